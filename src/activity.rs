@@ -0,0 +1,217 @@
+use crate::game::{ Game, PlayerAction, PLAYER_ID };
+use crate::gui::MessageLog;
+use crate::item::MAX_INVENTORY;
+use crate::path::{ self, DijkstraMap, Walker };
+use crate::ai::Ai;
+
+use tcod::colors;
+
+/// The most turns a single rest can last
+pub const REST_TURN_LIMIT: u32 = 300;
+
+/// Something the player keeps doing over several turns, one action at a time,
+/// until it's finished or interrupted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Activity
+{
+    /// Walk to the given position
+    Travel((i32, i32)),
+
+    /// Walk to the nearest unexplored tile until the whole level is explored,
+    /// then walk to the stairs
+    Explore,
+
+    /// Wait until healed, for at most the given number of turns
+    Rest(u32)
+}
+
+/// The activity the player is doing along with what the game looked like
+/// before its last action, which is what interruptions are checked against
+#[derive(Debug, Clone, Copy)]
+pub struct ActivityState
+{
+    activity: Activity,
+    hp: i32,
+    messages: usize,
+
+    /// The last action of the activity, since picking up an item adds a
+    /// message that shouldn't interrupt it
+    last_action: Option< PlayerAction >
+}
+
+/// Starts the given activity unless something would interrupt it straight away.
+/// Returns true if the activity was started.
+pub fn start_activity(activity: Activity, game: &mut Game) -> bool
+{
+    if !game.objects[PLAYER_ID].alive
+    {
+        return false;
+    }
+
+    if monster_in_view(game)
+    {
+        game.log.add("Not with monsters in view!", colors::YELLOW);
+        return false;
+    }
+
+    game.activity = Some(snapshot(activity, None, game));
+    true
+}
+
+/// Returns the next action of the player's activity, or None when there is
+/// no activity or it has finished or been interrupted, in which case it's
+/// stopped. The activity is interrupted by a monster coming into view, a new
+/// message or the player losing health.
+pub fn next_action(game: &mut Game) -> Option< PlayerAction >
+{
+    let state = game.activity.take()?;
+    if interrupted(&state, game)
+    {
+        return None;
+    }
+
+    let (activity, action) = match state.activity
+    {
+        Activity::Travel(goal) => (state.activity, travel_step(goal, game)),
+        Activity::Explore => (state.activity, explore_step(game)),
+        Activity::Rest(turns_left) => (Activity::Rest(turns_left.saturating_sub(1)), rest_step(turns_left, game))
+    };
+
+    if action.is_some()
+    {
+        game.activity = Some(snapshot(activity, action, game));
+    }
+    action
+}
+
+/// Returns the move that takes the player one step closer to the goal, going
+/// by what the player knows of the map. Stops next to whatever is standing in
+/// the way rather than walking into it.
+fn travel_step(goal: (i32, i32), game: &mut Game) -> Option< PlayerAction >
+{
+    let pos = game.objects[PLAYER_ID].pos;
+    if pos == goal
+    {
+        return None;
+    }
+
+    let (x, y) = *path::find_path(&game.map, &game.objects, pos, goal, Walker::Player)?.first()?;
+    if game.map.is_blocked(x, y, &game.objects)
+    {
+        game.log.add("There's something in the way.", colors::WHITE);
+        return None;
+    }
+
+    Some(PlayerAction::Move(x - pos.0, y - pos.1))
+}
+
+/// Returns a wait until the player is fully healed or has rested for as long
+/// as they can
+fn rest_step(turns_left: u32, game: &mut Game) -> Option< PlayerAction >
+{
+    let healed = game.objects[PLAYER_ID].fighter.as_ref().map_or(true, |f| f.hp >= f.max_hp);
+    if healed
+    {
+        game.log.add("You feel rested.", colors::WHITE);
+        None
+    }
+    else if turns_left == 0
+    {
+        game.log.add("You stop resting.", colors::WHITE);
+        None
+    }
+    else
+    {
+        Some(PlayerAction::Wait)
+    }
+}
+
+/// Returns the next action while exploring: picking up the item under the
+/// player when auto-pickup is on, otherwise a step towards the nearest
+/// unexplored tile (or wanted item), or towards the stairs once there's
+/// nothing left to explore
+fn explore_step(game: &mut Game) -> Option< PlayerAction >
+{
+    let pos = game.objects[PLAYER_ID].pos;
+    let wants_items = game.auto_pickup && game.objects[PLAYER_ID].inventory.len() < MAX_INVENTORY;
+    if wants_items && game.objects.values().any(|o| o.pos == pos && o.item.is_some())
+    {
+        return Some(PlayerAction::Interact);
+    }
+
+    // Unexplored floor is the edge of what's been explored, since walls are
+    // explored as soon as they're seen
+    let mut goals = vec![];
+    for x in 0..game.map.width
+    {
+        for y in 0..game.map.height
+        {
+            let tile = &game.map.tiles[x as usize][y as usize];
+            if !tile.explored && !tile.blocked
+            {
+                goals.push((x, y));
+            }
+        }
+    }
+    if wants_items
+    {
+        goals.extend(game.objects.values().filter(|o| o.item.is_some() && game.map.is_explored(o.pos)).map(|o| o.pos));
+    }
+
+    let explore_map = DijkstraMap::new(&game.map, &game.objects, &goals, Walker::Player);
+    if let Some((x, y)) = explore_map.next_step(&game.map, pos)
+    {
+        return Some(PlayerAction::Move(x - pos.0, y - pos.1));
+    }
+
+    // Nothing left to explore, so head for the stairs
+    let stairs = game.objects.values().find(|o| o.name == "Stairs" && game.map.is_explored(o.pos)).map(|o| o.pos);
+    match stairs
+    {
+        Some(stairs) if stairs != pos =>
+        {
+            if path::find_path(&game.map, &game.objects, pos, stairs, Walker::Player).is_none()
+            {
+                game.log.add("There's nothing left to explore that can be reached.", colors::WHITE);
+                return None;
+            }
+            travel_step(stairs, game)
+        }
+
+        _ =>
+        {
+            game.log.add("There's nothing left to explore.", colors::WHITE);
+            None
+        }
+    }
+}
+
+/// Returns the state of the activity as the game is right now
+fn snapshot(activity: Activity, last_action: Option< PlayerAction >, game: &Game) -> ActivityState
+{
+    ActivityState
+    {
+        activity: activity,
+        hp: game.objects[PLAYER_ID].fighter.as_ref().map_or(0, |f| f.hp),
+        messages: game.log.len(),
+        last_action: last_action
+    }
+}
+
+/// Returns true if anything happened since the activity's last action that
+/// should stop it
+fn interrupted(state: &ActivityState, game: &Game) -> bool
+{
+    let hp = game.objects[PLAYER_ID].fighter.as_ref().map_or(0, |f| f.hp);
+    let new_messages = game.log.len() > state.messages && state.last_action != Some(PlayerAction::Interact);
+    !game.objects[PLAYER_ID].alive || hp < state.hp || new_messages || monster_in_view(game)
+}
+
+/// Returns true if the player can see a living monster that's awake. Sleeping
+/// monsters don't stop activities until they wake up.
+fn monster_in_view(game: &Game) -> bool
+{
+    game.objects
+        .values()
+        .any(|o| o.alive && game.map.is_in_fov(o.pos) && o.ai.is_some() && !matches!(o.ai, Some(Ai::Sleeping { .. })))
+}
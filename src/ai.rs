@@ -1,28 +1,370 @@
 use crate::game::{ Game, PLAYER_ID };
 use crate::object;
+use crate::entity::ObjectId;
+use crate::fighter::StatusKind;
+use crate::gui::MessageLog;
+use crate::path::{ DijkstraMap, Walker };
+use crate::map::FOV_RADIUS;
+use crate::targeting;
+
+use tcod::colors;
+use rand::Rng;
+
+/// How far away the sounds of a fight can be heard
+pub const COMBAT_NOISE: i32 = 6;
+
+/// How far away thunder and explosions can be heard
+pub const MAGIC_NOISE: i32 = 10;
+
+/// The chance every turn that a sleeping monster in view of the player notices them
+const WAKE_CHANCE: f32 = 0.25;
+
+/// How far a pack hunter's call to the rest of its pack carries
+const PACK_CALL_RANGE: f32 = 10.0;
 
 /// Represents the different types of AI for monsters
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Ai
 {
+    /// Charges the player whenever it can see them
     BasicMonster,
+
+    /// Sleeps until it notices the player or hears a fight or spell nearby,
+    /// then wakes up with the given AI
+    Sleeping { awake: Box< Ai > },
+
+    /// Walks from room to room until it sees the player, then charges them.
+    /// `goal` is the center of the room it's walking to.
+    Wanderer
+    {
+        #[serde(default)]
+        goal: Option< (i32, i32) >
+    },
+
+    /// Attacks the player from up to `range` tiles away when it has a clear
+    /// shot, and backs away when the player gets next to it
+    Ranged { range: i32 },
+
+    /// Charges the player, but runs away while its health is below
+    /// `flee_below` percent
+    Coward
+    {
+        flee_below: i32,
+        #[serde(default)]
+        fleeing: bool
+    },
+
+    /// Calls the rest of its pack when it spots the player, and tries to
+    /// surround the player rather than queue up behind the others. `pack` tells
+    /// packs apart (pack hunters spawned in the same room are one pack) and
+    /// `target` is where the player was last seen by the pack.
+    PackHunter
+    {
+        #[serde(default)]
+        pack: usize,
+        #[serde(default)]
+        target: Option< (i32, i32) >
+    }
+}
+
+impl Ai
+{
+    /// Returns what the monster is doing if it's worth pointing out
+    pub fn description(&self) -> Option< &'static str >
+    {
+        match *self
+        {
+            Ai::Sleeping { .. } => Some("Asleep"),
+            Ai::Coward { fleeing: true, .. } => Some("Fleeing"),
+            _ => None
+        }
+    }
+}
+
+pub fn ai_basic_monster(id: ObjectId, game: &mut Game) -> Ai
+{
+    if sees_player(id, game)
+    {
+        charge(id, game);
+    }
+
+    Ai::BasicMonster
+}
+
+pub fn ai_sleeping(id: ObjectId, awake: Box< Ai >, game: &mut Game) -> Ai
+{
+    // Sleeping monsters can't be snuck past up close
+    let adjacent = game.objects[id].distance_to(&game.objects[PLAYER_ID]) < 2.0;
+    if adjacent || (sees_player(id, game) && game.rng.gen::< f32 >() < WAKE_CHANCE)
+    {
+        log_wake_up(id, game);
+        return *awake;
+    }
+
+    Ai::Sleeping { awake: awake }
+}
+
+pub fn ai_wanderer(id: ObjectId, goal: Option< (i32, i32) >, game: &mut Game) -> Ai
+{
+    // Once the player is out of sight again it picks a new room to wander to
+    if sees_player(id, game)
+    {
+        charge(id, game);
+        return Ai::Wanderer { goal: None };
+    }
+
+    let pos = game.objects[id].pos;
+    let goal = match goal
+    {
+        Some(goal) if goal != pos => goal,
+        _ =>
+        {
+            let rooms: Vec< (i32, i32) > = game.map.rooms.iter().cloned().filter(|&r| r != pos).collect();
+            if rooms.is_empty()
+            {
+                return Ai::Wanderer { goal: None };
+            }
+            rooms[game.rng.gen_range(0, rooms.len())]
+        }
+    };
+
+    // Give up on rooms it can't get any closer to
+    object::path_towards(id, goal.0, goal.1, game);
+    let moved = game.objects[id].pos != pos;
+    Ai::Wanderer { goal: if moved { Some(goal) } else { None } }
+}
+
+pub fn ai_ranged(id: ObjectId, range: i32, game: &mut Game) -> Ai
+{
+    if !sees_player(id, game)
+    {
+        return Ai::Ranged { range: range };
+    }
+
+    let pos = game.objects[id].pos;
+    let player_pos = game.objects[PLAYER_ID].pos;
+    let distance = game.objects[id].distance_to(&game.objects[PLAYER_ID]);
+    if distance < 2.0
+    {
+        // Back away, and only fight up close when cornered
+        if !flee_step(id, game) && !game.map.cuts_corner(pos, player_pos.0 - pos.0, player_pos.1 - pos.1)
+        {
+            attack_player(id, game);
+        }
+    }
+    else if distance <= range as f32 && targeting::line_of_fire(game, pos, player_pos).last() == Some(&player_pos)
+    {
+        attack_player(id, game);
+    }
+    else
+    {
+        object::path_towards(id, player_pos.0, player_pos.1, game);
+    }
+
+    Ai::Ranged { range: range }
+}
+
+pub fn ai_coward(id: ObjectId, flee_below: i32, fleeing: bool, game: &mut Game) -> Ai
+{
+    let health = game.objects[id].fighter.as_ref().map_or(100, |f| f.hp * 100 / f.max_hp);
+    let now_fleeing = health < flee_below;
+    if !sees_player(id, game)
+    {
+        return Ai::Coward { flee_below: flee_below, fleeing: now_fleeing };
+    }
+
+    if now_fleeing
+    {
+        if !fleeing && game.map.is_in_fov(game.objects[id].pos)
+        {
+            game.log.add(format!("The {} turns to flee!", game.objects[id].name), colors::LIGHT_GREY);
+        }
+
+        // A cornered coward still fights back
+        let pos = game.objects[id].pos;
+        let player_pos = game.objects[PLAYER_ID].pos;
+        let adjacent = game.objects[id].distance_to(&game.objects[PLAYER_ID]) < 2.0;
+        if !flee_step(id, game) && adjacent && !game.map.cuts_corner(pos, player_pos.0 - pos.0, player_pos.1 - pos.1)
+        {
+            attack_player(id, game);
+        }
+    }
+    else
+    {
+        charge(id, game);
+    }
+
+    Ai::Coward { flee_below: flee_below, fleeing: now_fleeing }
 }
 
-pub fn ai_basic_monster(id: usize, game: &mut Game) -> Ai
+pub fn ai_pack_hunter(id: ObjectId, pack: usize, target: Option< (i32, i32) >, game: &mut Game) -> Ai
+{
+    let player_pos = game.objects[PLAYER_ID].pos;
+    if !sees_player(id, game)
+    {
+        // Head for where the pack last saw the player and give up there
+        let target = match target
+        {
+            Some(target) if target != game.objects[id].pos => target,
+            _ => return Ai::PackHunter { pack: pack, target: None }
+        };
+
+        let pos = game.objects[id].pos;
+        object::path_towards(id, target.0, target.1, game);
+        let moved = game.objects[id].pos != pos;
+        return Ai::PackHunter { pack: pack, target: if moved { Some(target) } else { None } };
+    }
+
+    call_pack(id, pack, player_pos, game);
+
+    let pos = game.objects[id].pos;
+    let adjacent = game.objects[id].distance_to(&game.objects[PLAYER_ID]) < 2.0;
+    if adjacent && !game.map.cuts_corner(pos, player_pos.0 - pos.0, player_pos.1 - pos.1)
+    {
+        attack_player(id, game);
+        return Ai::PackHunter { pack: pack, target: Some(player_pos) };
+    }
+
+    // Head for the closest free tile around the player it could attack from,
+    // so the pack spreads out around the player
+    let mut goals = vec![];
+    for dx in -1..=1
+    {
+        for dy in -1..=1
+        {
+            let tile = (player_pos.0 + dx, player_pos.1 + dy);
+            if (dx, dy) != (0, 0) && !game.map.is_blocked(tile.0, tile.1, &game.objects) && !game.map.cuts_corner(tile, -dx, -dy)
+            {
+                goals.push(tile);
+            }
+        }
+    }
+
+    let surround_map = DijkstraMap::new(&game.map, &game.objects, &goals, Walker::Monster { monsters_passable: false });
+    match surround_map.next_step(&game.map, pos)
+    {
+        Some((x, y)) => object::move_by(id, x - pos.0, y - pos.1, game),
+        None => object::path_towards(id, player_pos.0, player_pos.1, game)
+    }
+
+    Ai::PackHunter { pack: pack, target: Some(player_pos) }
+}
+
+/// Stumbles in a random direction, for monsters that are confused
+pub fn ai_confused(id: ObjectId, game: &mut Game)
+{
+    let dx = game.rng.gen_range(-1, 2);
+    let dy = game.rng.gen_range(-1, 2);
+    object::move_by(id, dx, dy, game);
+}
+
+/// Wakes up every sleeping monster within the given radius of a noise
+pub fn make_noise(pos: (i32, i32), radius: i32, game: &mut Game)
+{
+    let sleepers: Vec< ObjectId > = game.objects
+        .iter()
+        .filter(|(_, o)| o.alive && o.distance(pos.0, pos.1) <= radius as f32)
+        .filter(|(_, o)| matches!(o.ai, Some(Ai::Sleeping { .. })))
+        .map(|(id, _)| id)
+        .collect();
+
+    for id in sleepers
+    {
+        if let Some(Ai::Sleeping { awake }) = game.objects[id].ai.take()
+        {
+            game.objects[id].ai = Some(*awake);
+            log_wake_up(id, game);
+        }
+    }
+}
+
+/// Returns true if the monster can see the player. Monsters see as far as an
+/// unblinded player does, whatever the player's own sight is, and blind
+/// monsters only notice the player when they bump into them.
+fn sees_player(id: ObjectId, game: &Game) -> bool
+{
+    let distance = game.objects[id].distance_to(&game.objects[PLAYER_ID]);
+    let blind = game.objects[id].fighter.as_ref().map_or(false, |f| f.has_effect(StatusKind::Blindness));
+    if blind
+    {
+        distance < 2.0
+    }
+    else
+    {
+        distance <= FOV_RADIUS as f32 && targeting::line_of_sight(game, game.objects[id].pos, game.objects[PLAYER_ID].pos)
+    }
+}
+
+/// Moves towards the player, attacking once next to them
+fn charge(id: ObjectId, game: &mut Game)
 {
     let (mx, my) = game.objects[id].pos;
-    if game.map.is_in_fov((mx, my))
+    let (px, py) = game.objects[PLAYER_ID].pos;
+    if game.objects[id].distance_to(&game.objects[PLAYER_ID]) >= 2.0 || game.map.cuts_corner((mx, my), px - mx, py - my)
     {
-        if game.objects[id].distance_to(&game.objects[PLAYER_ID]) >= 2.0
+        object::path_towards(id, px, py, game);
+    }
+    else
+    {
+        attack_player(id, game);
+    }
+}
+
+/// Attacks the player, waking up anything sleeping nearby
+fn attack_player(id: ObjectId, game: &mut Game)
+{
+    if game.objects[PLAYER_ID].fighter.as_ref().map_or(false, |f| f.hp > 0)
+    {
+        let player_name = game.objects[PLAYER_ID].name.clone();
+        let (monster, player) = game.objects.mut_two(id, PLAYER_ID);
+        if monster.attack(player, &mut game.log)
         {
-            object::move_towards(id, game.objects[PLAYER_ID].pos.0, game.objects[PLAYER_ID].pos.1, game);
+            game.record_death(id, PLAYER_ID, player_name);
         }
-        else if game.objects[PLAYER_ID].fighter.map_or(false, |f| f.hp > 0)
+
+        let player_pos = game.objects[PLAYER_ID].pos;
+        make_noise(player_pos, COMBAT_NOISE, game);
+    }
+}
+
+/// Takes a step away from the player. Returns false if there's nowhere further
+/// away to go.
+fn flee_step(id: ObjectId, game: &mut Game) -> bool
+{
+    let pos = game.objects[id].pos;
+    let player_pos = game.objects[PLAYER_ID].pos;
+    let flee_map = DijkstraMap::flee(&game.map, &game.objects, &[player_pos], Walker::Monster { monsters_passable: true });
+    if let Some((x, y)) = flee_map.next_step(&game.map, pos)
+    {
+        object::move_by(id, x - pos.0, y - pos.1, game);
+    }
+
+    game.objects[id].pos != pos
+}
+
+/// Tells the rest of the monster's pack nearby where the player is
+fn call_pack(id: ObjectId, pack: usize, player_pos: (i32, i32), game: &mut Game)
+{
+    let pos = game.objects[id].pos;
+    for other_id in game.objects.ids().into_iter().filter(|&other_id| other_id != id)
+    {
+        let other = &mut game.objects[other_id];
+        if other.alive && other.distance(pos.0, pos.1) <= PACK_CALL_RANGE
         {
-            let (monster, player) = object::mut_two(id, PLAYER_ID, &mut game.objects);
-            monster.attack(player, &game.inventory, &mut game.log);
+            match other.ai
+            {
+                Some(Ai::PackHunter { pack: other_pack, ref mut target }) if other_pack == pack => *target = Some(player_pos),
+                _ => {}
+            }
         }
     }
+}
 
-    Ai::BasicMonster
-}
\ No newline at end of file
+/// Lets the player know a monster they can see woke up
+fn log_wake_up(id: ObjectId, game: &mut Game)
+{
+    if game.map.is_in_fov(game.objects[id].pos)
+    {
+        game.log.add(format!("The {} wakes up!", game.objects[id].name), colors::ORANGE);
+    }
+}
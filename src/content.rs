@@ -0,0 +1,239 @@
+use crate::object::Object;
+use crate::fighter::{ Fighter, DeathCallback, NORMAL_SPEED };
+use crate::ai::Ai;
+use crate::item::{ Item, Equipment };
+use crate::map::Transition;
+use crate::game::STARTING_GEAR;
+
+use tcod::colors::Color;
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+
+const MONSTERS_FILE: &str = "./res/monsters.json";
+const ITEMS_FILE: &str = "./res/items.json";
+
+/// All of the game content that's loaded from the content files in `res/`
+#[derive(Debug, Clone, Default)]
+pub struct Content
+{
+    pub monsters: Vec< MonsterTemplate >,
+    pub items: Vec< ItemTemplate >
+}
+
+impl Content
+{
+    /// Returns the item template with the given name
+    pub fn item(&self, name: &str) -> Option< &ItemTemplate >
+    {
+        self.items.iter().find(|i| i.name == name)
+    }
+}
+
+/// The base stats of a fighter as written in the content files
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Stats
+{
+    pub vit: i32,
+    pub atk: i32,
+    pub str: i32,
+    pub def: i32,
+    pub dex: i32,
+    pub int: i32,
+    pub lck: i32
+}
+
+/// Describes a kind of monster and how likely it is to spawn at each dungeon level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonsterTemplate
+{
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+    pub stats: Stats,
+
+    #[serde(default = "default_speed")]
+    pub speed: i32,
+
+    pub ai: Ai,
+
+    /// Experience the player is awarded for killing the monster
+    pub xp: i32,
+
+    /// Spawn weight of the monster by dungeon level
+    pub weights: Vec< Transition >
+}
+
+impl MonsterTemplate
+{
+    /// Creates a living monster object from this template at the given position
+    pub fn spawn(&self, x: i32, y: i32) -> Object
+    {
+        let s = self.stats;
+        let mut monster = Object::new(x, y, self.glyph, self.color, &self.name, true);
+        let mut fighter = Fighter::new(s.vit, s.atk, s.str, s.def, s.dex, s.int, s.lck, self.xp, DeathCallback::MonsterDeath);
+        fighter.speed = self.speed;
+        monster.fighter = Some(fighter);
+        monster.ai = Some(self.ai.clone());
+        monster.alive = true;
+
+        monster
+    }
+}
+
+/// Describes a kind of item and how likely it is to spawn at each dungeon level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemTemplate
+{
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+
+    /// What happens when the item is used
+    pub effect: Item,
+
+    /// The slot and stat bonuses of the item if it can be equipped
+    #[serde(default)]
+    pub equipment: Option< Equipment >,
+
+    /// Spawn weight of the item by dungeon level
+    pub weights: Vec< Transition >
+}
+
+impl ItemTemplate
+{
+    /// Creates an item object from this template at the given position
+    pub fn spawn(&self, x: i32, y: i32) -> Object
+    {
+        let mut item = Object::new(x, y, self.glyph, self.color, &self.name, false);
+        item.item = Some(self.effect);
+        item.equipment = self.equipment.map(|e| Equipment { equipped: false, ..e });
+
+        item
+    }
+}
+
+fn default_speed() -> i32
+{
+    NORMAL_SPEED
+}
+
+/// Loads and validates all of the content files
+pub fn load_content() -> Result< Content, Box< dyn Error > >
+{
+    let monsters: Vec< MonsterTemplate > = load_file(MONSTERS_FILE)?;
+    validate_monsters(&monsters).map_err(|e| format!("{}: {}", MONSTERS_FILE, e))?;
+
+    let items: Vec< ItemTemplate > = load_file(ITEMS_FILE)?;
+    validate_items(&items).map_err(|e| format!("{}: {}", ITEMS_FILE, e))?;
+
+    Ok(Content { monsters: monsters, items: items })
+}
+
+/// Deserializes a single content file
+fn load_file< T: serde::de::DeserializeOwned >(path: &str) -> Result< T, Box< dyn Error > >
+{
+    let mut json = String::new();
+    let mut file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    file.read_to_string(&mut json)?;
+    let result = serde_json::from_str::< T >(&json).map_err(|e| format!("{}: {}", path, e))?;
+
+    Ok(result)
+}
+
+/// Checks that the monster templates make sense
+fn validate_monsters(monsters: &[MonsterTemplate]) -> Result< (), String >
+{
+    if monsters.is_empty()
+    {
+        return Err("there has to be at least one monster".into());
+    }
+
+    let mut names = HashSet::new();
+    for monster in monsters
+    {
+        if !names.insert(&monster.name)
+        {
+            return Err(format!("there is more than one monster named {}", monster.name));
+        }
+
+        if monster.speed <= 0
+        {
+            return Err(format!("{} must have a speed above 0", monster.name));
+        }
+
+        validate_ai(&monster.name, &monster.ai)?;
+        validate_weights(&monster.name, &monster.weights)?;
+    }
+
+    Ok(())
+}
+
+/// Checks that a monster's AI makes sense, including the AI it wakes up with
+fn validate_ai(name: &str, ai: &Ai) -> Result< (), String >
+{
+    match *ai
+    {
+        Ai::Sleeping { ref awake } => validate_ai(name, awake),
+        Ai::Ranged { range } if range < 2 => Err(format!("{} must have a range of at least 2", name)),
+        Ai::Coward { flee_below, .. } if flee_below <= 0 || flee_below > 100 => Err(format!("{} must flee below a health percentage between 1 and 100", name)),
+        _ => Ok(())
+    }
+}
+
+/// Checks that the item templates make sense
+fn validate_items(items: &[ItemTemplate]) -> Result< (), String >
+{
+    let mut names = HashSet::new();
+    for item in items
+    {
+        if !names.insert(&item.name)
+        {
+            return Err(format!("there is more than one item named {}", item.name));
+        }
+
+        match (item.effect, item.equipment.is_some())
+        {
+            (Item::Equip, false) => return Err(format!("{} is equipped when used but has no equipment", item.name)),
+            (Item::Equip, true) => {},
+            (_, true) => return Err(format!("{} has equipment but isn't equipped when used", item.name)),
+            (Item::Heal { amount }, false) if amount <= 0 => return Err(format!("{} must heal more than 0", item.name)),
+            (Item::Lightning { damage, range }, false) if damage <= 0 || range <= 0 => return Err(format!("{} must have a damage and range above 0", item.name)),
+            (Item::Fireball { damage, radius, range }, false) if damage <= 0 || radius < 0 || range <= 0 => return Err(format!("{} must have a damage and range above 0 and a radius of at least 0", item.name)),
+            (Item::Confusion { turns, range }, false) if turns <= 0 || range <= 0 => return Err(format!("{} must last more than 0 turns and have a range above 0", item.name)),
+            (Item::Status { turns, .. }, false) if turns <= 0 => return Err(format!("{} must last more than 0 turns", item.name)),
+            (_, false) => {}
+        }
+
+        validate_weights(&item.name, &item.weights)?;
+    }
+
+    // The player's starting gear has to exist
+    for name in STARTING_GEAR
+    {
+        if !items.iter().any(|i| i.name == *name)
+        {
+            return Err(format!("the starting item {} is missing", name));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that a spawn weight table is sorted by level and has no negative weights
+fn validate_weights(name: &str, weights: &[Transition]) -> Result< (), String >
+{
+    if weights.windows(2).any(|w| w[0].level >= w[1].level)
+    {
+        return Err(format!("the spawn weights of {} must be sorted by level", name));
+    }
+
+    if weights.iter().any(|t| t.value < 0)
+    {
+        return Err(format!("{} has a negative spawn weight", name));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,306 @@
+use crate::object::Object;
+
+use std::cmp;
+use std::ops::{ Index, IndexMut };
+
+/// A stable handle to an object in an `Objects` store. Handles stay valid no
+/// matter what else is added to or removed from the store, and a handle to a
+/// removed object can never end up pointing at a different object since slots
+/// get a new generation every time they're reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ObjectId
+{
+    index: u32,
+    generation: u32
+}
+
+impl ObjectId
+{
+    pub const fn new(index: u32, generation: u32) -> Self
+    {
+        ObjectId { index: index, generation: generation }
+    }
+}
+
+/// A single slot in the store, holding an object or waiting to be reused
+#[derive(Serialize, Deserialize)]
+struct Slot
+{
+    generation: u32,
+    object: Option< Object >
+}
+
+/// Generational store for all of the objects on the map
+#[derive(Serialize, Deserialize)]
+pub struct Objects
+{
+    slots: Vec< Slot >,
+
+    /// Indices of the empty slots, rebuilt after loading by `rebuild_free_list`
+    #[serde(skip)]
+    free: Vec< u32 >
+}
+
+impl Objects
+{
+    /// Creates a new, empty object store
+    pub fn new() -> Self
+    {
+        Objects { slots: vec![], free: vec![] }
+    }
+
+    /// Adds an object to the store and returns the handle to it
+    pub fn insert(&mut self, object: Object) -> ObjectId
+    {
+        if let Some(index) = self.free.pop()
+        {
+            let slot = &mut self.slots[index as usize];
+            slot.object = Some(object);
+            ObjectId::new(index, slot.generation)
+        }
+        else
+        {
+            self.slots.push(Slot { generation: 0, object: Some(object) });
+            ObjectId::new(self.slots.len() as u32 - 1, 0)
+        }
+    }
+
+    /// Removes the object with the given handle from the store, returning it.
+    /// Returns None if the handle doesn't point to a live object.
+    pub fn remove(&mut self, id: ObjectId) -> Option< Object >
+    {
+        if !self.contains(id)
+        {
+            return None;
+        }
+
+        let slot = &mut self.slots[id.index as usize];
+        slot.generation += 1;
+        self.free.push(id.index);
+        slot.object.take()
+    }
+
+    /// Rebuilds the list of empty slots from the slots themselves
+    pub fn rebuild_free_list(&mut self)
+    {
+        self.free = self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.object.is_none())
+            .map(|(i, _)| i as u32)
+            .collect();
+    }
+
+    /// Removes every object that the given predicate returns false for
+    pub fn retain< F: FnMut(ObjectId, &Object) -> bool >(&mut self, mut f: F)
+    {
+        for id in self.ids()
+        {
+            if !f(id, &self[id])
+            {
+                self.remove(id);
+            }
+        }
+    }
+
+    /// Returns true if the given handle points to a live object
+    pub fn contains(&self, id: ObjectId) -> bool
+    {
+        self.get(id).is_some()
+    }
+
+    /// Returns the object with the given handle, if it's still in the store
+    pub fn get(&self, id: ObjectId) -> Option< &Object >
+    {
+        self.slots
+            .get(id.index as usize)
+            .filter(|s| s.generation == id.generation)
+            .and_then(|s| s.object.as_ref())
+    }
+
+    /// Mutably returns the object with the given handle, if it's still in the store
+    pub fn get_mut(&mut self, id: ObjectId) -> Option< &mut Object >
+    {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|s| s.generation == id.generation)
+            .and_then(|s| s.object.as_mut())
+    }
+
+    /// Returns the handles of every object currently in the store. The returned
+    /// list is a snapshot, so the store can be changed while iterating over it.
+    pub fn ids(&self) -> Vec< ObjectId >
+    {
+        self.iter().map(|(id, _)| id).collect()
+    }
+
+    /// Iterates over every object in the store along with its handle
+    pub fn iter(&self) -> impl Iterator< Item = (ObjectId, &Object) >
+    {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| s.object.as_ref().map(|o| (ObjectId::new(i as u32, s.generation), o)))
+    }
+
+    /// Iterates over every object in the store
+    pub fn values(&self) -> impl Iterator< Item = &Object >
+    {
+        self.slots.iter().filter_map(|s| s.object.as_ref())
+    }
+
+    /// Mutably borrow two *separate* objects from the store.
+    /// Panics when the handles are equal or don't point to live objects.
+    pub fn mut_two(&mut self, first: ObjectId, second: ObjectId) -> (&mut Object, &mut Object)
+    {
+        assert!(first != second);
+        assert!(self.contains(first) && self.contains(second), "Cannot borrow a removed object!");
+
+        let (first_index, second_index) = (first.index as usize, second.index as usize);
+        let split_at_index = cmp::max(first_index, second_index);
+        let (first_slice, second_slice) = self.slots.split_at_mut(split_at_index);
+        let (first_slot, second_slot) = if first_index < second_index
+        {
+            (&mut first_slice[first_index], &mut second_slice[0])
+        }
+        else
+        {
+            (&mut second_slice[0], &mut first_slice[second_index])
+        };
+
+        (first_slot.object.as_mut().unwrap(), second_slot.object.as_mut().unwrap())
+    }
+}
+
+impl Index< ObjectId > for Objects
+{
+    type Output = Object;
+
+    fn index(&self, id: ObjectId) -> &Object
+    {
+        self.get(id).expect("Object handle no longer points to a live object!")
+    }
+}
+
+impl IndexMut< ObjectId > for Objects
+{
+    fn index_mut(&mut self, id: ObjectId) -> &mut Object
+    {
+        self.get_mut(id).expect("Object handle no longer points to a live object!")
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use tcod::colors;
+
+    fn object(name: &str) -> Object
+    {
+        Object::new(0, 0, 'o', colors::WHITE, name, false)
+    }
+
+    #[test]
+    fn removed_slots_are_reused_with_a_new_generation()
+    {
+        let mut objects = Objects::new();
+        let first = objects.insert(object("first"));
+        objects.insert(object("second"));
+
+        assert_eq!(objects.remove(first).map(|o| o.name), Some("first".to_string()));
+        let third = objects.insert(object("third"));
+
+        assert_eq!(third.index, first.index);
+        assert_eq!(third.generation, first.generation + 1);
+        assert_eq!(objects.slots.len(), 2);
+    }
+
+    #[test]
+    fn stale_handles_point_to_nothing()
+    {
+        let mut objects = Objects::new();
+        let stale = objects.insert(object("first"));
+        objects.remove(stale);
+        let reused = objects.insert(object("second"));
+
+        assert!(!objects.contains(stale));
+        assert!(objects.get(stale).is_none());
+        assert!(objects.get_mut(stale).is_none());
+        assert!(objects.remove(stale).is_none());
+        assert_eq!(objects[reused].name, "second");
+    }
+
+    #[test]
+    fn retain_removes_the_rejected_objects()
+    {
+        let mut objects = Objects::new();
+        let keep = objects.insert(object("keep"));
+        let rejected = objects.insert(object("drop"));
+        let also_keep = objects.insert(object("keep"));
+
+        objects.retain(|_, o| o.name == "keep");
+
+        assert_eq!(objects.ids(), vec![keep, also_keep]);
+        assert!(!objects.contains(rejected));
+        assert_eq!(objects.free, vec![rejected.index]);
+    }
+
+    #[test]
+    fn loaded_stores_reuse_their_empty_slots()
+    {
+        let mut objects = Objects::new();
+        objects.insert(object("first"));
+        let removed = objects.insert(object("second"));
+        objects.insert(object("third"));
+        objects.remove(removed);
+
+        let json = serde_json::to_string(&objects).expect("Failed to serialize the objects!");
+        let mut loaded: Objects = serde_json::from_str(&json).expect("Failed to deserialize the objects!");
+        assert!(loaded.free.is_empty());
+
+        loaded.rebuild_free_list();
+        let inserted = loaded.insert(object("fourth"));
+
+        assert_eq!(inserted.index, removed.index);
+        assert!(!loaded.contains(removed));
+        assert_eq!(loaded.slots.len(), 3);
+    }
+
+    #[test]
+    fn mut_two_borrows_both_objects_in_either_order()
+    {
+        let mut objects = Objects::new();
+        let first = objects.insert(object("first"));
+        let second = objects.insert(object("second"));
+
+        {
+            let (a, b) = objects.mut_two(first, second);
+            assert_eq!((a.name.as_str(), b.name.as_str()), ("first", "second"));
+            a.name = "changed".into();
+        }
+        let (b, a) = objects.mut_two(second, first);
+        assert_eq!((a.name.as_str(), b.name.as_str()), ("changed", "second"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn mut_two_panics_on_the_same_object()
+    {
+        let mut objects = Objects::new();
+        let id = objects.insert(object("first"));
+        objects.mut_two(id, id);
+    }
+
+    #[test]
+    #[should_panic]
+    fn mut_two_panics_on_a_removed_object()
+    {
+        let mut objects = Objects::new();
+        let first = objects.insert(object("first"));
+        let second = objects.insert(object("second"));
+        objects.remove(second);
+        objects.mut_two(first, second);
+    }
+}
@@ -2,8 +2,24 @@ use crate::object::Object;
 use crate::gui::{ Messages, MessageLog};
 
 use tcod::colors;
+use std::cmp;
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// The speed of a normal fighter; it gains enough energy to act once per tick
+pub const NORMAL_SPEED: i32 = 100;
+
+/// The amount of energy a fighter needs to spend to act
+pub const ACTION_COST: i32 = 100;
+
+/// The amount of regeneration a fighter needs to build up to heal a point of HP
+pub const REGEN_COST: i32 = 40;
+
+/// The HP lost every turn while poisoned
+const POISON_DAMAGE: i32 = 1;
+
+/// The HP healed every turn by the regeneration effect
+const REGENERATION_HEAL: i32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Fighter
 {
     pub base_vit: i32,  // Vitality - hitpoints
@@ -18,6 +34,15 @@ pub struct Fighter
     pub hp: i32,
     pub xp: i32,
 
+    pub speed: i32,     // Energy gained per tick, see NORMAL_SPEED
+    pub energy: i32,    // Energy saved up, the fighter can act once it has ACTION_COST
+
+    #[serde(default)]
+    pub regen: i32,     // Regeneration built up, heals a point of HP for every REGEN_COST
+
+    #[serde(default)]
+    pub effects: Vec< StatusEffect >,
+
     pub on_death: DeathCallback
 }
 
@@ -42,9 +67,176 @@ impl Fighter
             hp: max_hp,
             xp: xp,
 
+            speed: NORMAL_SPEED,
+            energy: 0,
+            regen: 0,
+            effects: vec![],
+
             on_death: on_death
         }
     }
+
+    /// Builds up regeneration from the given vitality, called once for every
+    /// turn the player takes, and heals the fighter once enough has built up.
+    /// Nothing builds up at full health.
+    pub fn regenerate(&mut self, vitality: i32)
+    {
+        if self.hp <= 0 || self.hp >= self.max_hp
+        {
+            self.regen = 0;
+            return;
+        }
+
+        self.regen += cmp::max(vitality, 1);
+        self.hp = cmp::min(self.hp + self.regen / REGEN_COST, self.max_hp);
+        self.regen %= REGEN_COST;
+    }
+
+    /// Returns true if the fighter is under the given status effect
+    pub fn has_effect(&self, kind: StatusKind) -> bool
+    {
+        self.effects.iter().any(|e| e.kind == kind)
+    }
+
+    /// Puts the fighter under the given status effect. An effect the fighter is
+    /// already under lasts for whichever is longer of the two.
+    pub fn add_effect(&mut self, kind: StatusKind, turns: i32)
+    {
+        match self.effects.iter_mut().find(|e| e.kind == kind)
+        {
+            Some(effect) => effect.turns = cmp::max(effect.turns, turns),
+            None => self.effects.push(StatusEffect { kind: kind, turns: turns })
+        }
+    }
+
+    /// Returns how much the fighter's status effects raise (or lower) the given stat
+    pub fn effect_modifier(&self, stat: Stat) -> i32
+    {
+        self.effects.iter().map(|e| e.kind.modifier(stat)).sum()
+    }
+
+    /// Applies the fighter's status effects for a turn and counts them down.
+    /// Returns the effects that wore off.
+    pub fn tick_effects(&mut self) -> Vec< StatusKind >
+    {
+        for effect in &self.effects
+        {
+            match effect.kind
+            {
+                StatusKind::Poison => self.hp -= POISON_DAMAGE,
+                StatusKind::Regeneration => self.hp = cmp::min(self.hp + REGENERATION_HEAL, self.max_hp),
+                _ => {}
+            }
+        }
+
+        for effect in self.effects.iter_mut()
+        {
+            effect.turns -= 1;
+        }
+
+        let expired = self.effects.iter().filter(|e| e.turns <= 0).map(|e| e.kind).collect();
+        self.effects.retain(|e| e.turns > 0);
+
+        expired
+    }
+}
+
+/// A status effect on a fighter and how many more turns it lasts
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect
+{
+    pub kind: StatusKind,
+    pub turns: i32
+}
+
+/// The different status effects a fighter can be under
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StatusKind
+{
+    /// Loses HP every turn
+    Poison,
+
+    /// Heals HP every turn
+    Regeneration,
+
+    /// Moves in random directions
+    Confusion,
+
+    /// Can't move or attack, and is easier to hit
+    Stun,
+
+    /// Gains energy twice as fast
+    Haste,
+
+    /// Gains energy half as fast
+    Slow,
+
+    /// Can barely see, which makes fighting harder
+    Blindness,
+
+    /// Hits less often and less hard
+    Weakness
+}
+
+impl StatusKind
+{
+    /// Returns how much the effect raises (or lowers) the given stat
+    pub fn modifier(self, stat: Stat) -> i32
+    {
+        match (self, stat)
+        {
+            (StatusKind::Stun, Stat::Defense) => -2,
+            (StatusKind::Stun, Stat::Dexterity) => -5,
+            (StatusKind::Blindness, Stat::Attack) => -3,
+            (StatusKind::Blindness, Stat::Dexterity) => -3,
+            (StatusKind::Weakness, Stat::Attack) => -2,
+            (StatusKind::Weakness, Stat::Strength) => -3,
+            _ => 0
+        }
+    }
+
+    /// Returns the short name of the effect shown in the GUI
+    pub fn name(self) -> &'static str
+    {
+        match self
+        {
+            StatusKind::Poison => "Poisoned",
+            StatusKind::Regeneration => "Regenerating",
+            StatusKind::Confusion => "Confused",
+            StatusKind::Stun => "Stunned",
+            StatusKind::Haste => "Hasted",
+            StatusKind::Slow => "Slowed",
+            StatusKind::Blindness => "Blind",
+            StatusKind::Weakness => "Weakened"
+        }
+    }
+}
+
+/// Represents one of a fighter's base stats
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Stat
+{
+    Vitality,
+    Attack,
+    Strength,
+    Defense,
+    Dexterity,
+    Intelligence,
+    Luck
+}
+
+impl Stat
+{
+    /// All of the stats, in the order they're listed in menus
+    pub const ALL: [Stat; 7] = [
+        Stat::Vitality, 
+        Stat::Attack, 
+        Stat::Strength, 
+        Stat::Defense, 
+        Stat::Dexterity, 
+        Stat::Intelligence, 
+        Stat::Luck
+    ];
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -77,7 +269,7 @@ fn player_death_callback(player: &mut Object, log: &mut Messages)
 
 fn monster_death_callback(monster: &mut Object, log: &mut Messages)
 {
-    log.add(format!("The {} has died! You gain {} experience points.", monster.name, monster.fighter.unwrap().xp), colors::PINK);
+    log.add(format!("The {} has died! You gain {} experience points.", monster.name, monster.fighter.as_ref().unwrap().xp), colors::PINK);
     monster.c = '%';
     monster.color = colors::DARK_RED;
     monster.name = format!("Remains of {}", monster.name);
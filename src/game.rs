@@ -1,51 +1,107 @@
 use crate::TCOD;
-use crate::map::Map;
+use crate::map::{ Map, FOV_RADIUS, BLIND_FOV_RADIUS };
 use crate::object::{ self, Object };
-use crate::fighter::{ Fighter, DeathCallback };
-use crate::item::{ self, * };
+use crate::entity::{ ObjectId, Objects };
+use crate::fighter::{ Fighter, DeathCallback, Stat, StatusKind, ACTION_COST };
+use crate::item;
 use crate::ai::{ self, Ai };
 use crate::menu;
 use crate::gui::{ self, * };
+use crate::replay::ReplayRecorder;
+use crate::content::Content;
+use crate::save::{ self, SaveError };
+use crate::morgue::{ self, RunEnd };
+use crate::keymap::Command;
+use crate::look;
+use crate::targeting;
+use crate::activity::{ self, Activity, ActivityState, REST_TURN_LIMIT };
 
 use tcod::colors;
 use tcod::console::*;
 use tcod::input::{ Key, KeyCode, Event };
+use rand::{ Rng, SeedableRng };
+use std::cmp::Reverse;
+use rand_pcg::Pcg32;
 
-use std::error::Error;
-use std::fs::File;
-use std::io::{ Read, Write };
 
-pub const PLAYER_ID: usize = 0;
+pub const PLAYER_ID: ObjectId = ObjectId::new(0, 0);
 pub const LEVEL_UP_BASE: i32 = 200;
 pub const LEVEL_UP_FACTOR: i32 = 150;
 
-/// Deserializes a game save file and loads a game from the data
-pub fn load_game() -> Result< Game, Box< Error > >
-{
-    let mut json_save = String::new();
-    let mut file = File::open("savegame")?;
-    file.read_to_string(&mut json_save)?;
-    let result = serde_json::from_str::< Game >(&json_save)?;
+/// The game is autosaved every time the player has taken this many turns
+pub const AUTOSAVE_INTERVAL: u32 = 100;
 
-    Ok(result)
-}
+/// The names of the item templates the player starts the game with
+pub const STARTING_GEAR: &[&str] = &["Dagger"];
+
+/// The random number generator used for everything in a game. It is seeded
+/// once when the game is created and saved along with it, so the same seed
+/// always produces the same dungeon.
+pub type GameRng = Pcg32;
 
 /// Represents an instance of the game
 #[derive(Serialize, Deserialize)]
 pub struct Game
 {
     pub map: Map,
-    pub objects: Vec< Object >,
-    pub inventory: Vec< Object >,
+    pub objects: Objects,
     pub log: Messages,
     pub dungeon_level: i32,
+
+    /// The number of turns the player has taken
+    #[serde(default)]
+    pub turns: u32,
+
+    #[serde(default)]
+    pub mode: GameMode,
+
+    /// The names of every monster the player has killed
+    #[serde(default)]
+    pub kills: Vec< String >,
+
+    /// What killed the player, once they're dead
+    #[serde(default)]
+    pub death_cause: Option< String >,
+
+    /// Whether exploring picks up the items it comes across
+    #[serde(default)]
+    pub auto_pickup: bool,
+
+    pub seed: u64,
+    pub rng: GameRng,
+
+    /// Every action fed into `step` so far, so the run can be replayed from the seed
+    pub actions: Vec< PlayerAction >,
+
+    /// False for games that weren't created from their seed (saves migrated
+    /// from before games were seeded), which can't be replayed
+    #[serde(default = "default_replayable")]
+    pub replayable: bool,
+
+    /// The monster templates etc. loaded from the content files
+    #[serde(skip)]
+    pub content: Content,
+
+    /// The save slot the game is saved to
+    #[serde(skip)]
+    pub slot: usize,
+
+    /// Set when the game should be autosaved, which is up to the frontend
+    #[serde(skip)]
+    pub autosave_due: bool,
+
+    /// What the player is doing over several turns, like travelling
+    #[serde(skip)]
+    pub activity: Option< ActivityState >,
 }
 
 impl Game
 {
-    /// Creates a new game
-    pub fn new() -> Self
+    /// Creates a new game from the given seed using the given game content
+    pub fn new(seed: u64, content: Content) -> Self
     {
+        let mut rng = GameRng::seed_from_u64(seed);
+
         // Create player
         // TODO: character creation to determine player name and stats?
         let mut player = Object::new(0, 0, '@', colors::WHITE, "Player", true);
@@ -53,107 +109,431 @@ impl Game
         // TODO: balance player stats
         player.fighter = Some(Fighter::new(5, 5, 5, 5, 5, 5, 5, 0, DeathCallback::PlayerDeath));
 
-        // Create objects vec
-        let mut objects = vec![ player ];
+        // The player gets to act first
+        player.fighter.as_mut().unwrap().energy = ACTION_COST;
+
+        // Give the player their starting gear
+        for name in STARTING_GEAR
+        {
+            let gear = content.item(name).expect("Missing starting item!").spawn(0, 0);
+            player.inventory.push(gear);
+        }
+
+        // Create object store, the player is always the first object in it
+        let mut objects = Objects::new();
+        objects.insert(player);
 
         // Create initial map & set player starting position
         let mut map = Map::new();
-        map.generate(&mut objects, 1);
-
-        // Create inventory w/ starting gear
-        let mut inventory: Vec< Object > = vec![];
-
-        let mut dagger = Object::new(0, 0, '-', colors::BRASS, "Dagger", false);
-        dagger.item = Some(Item::Sword);
-        dagger.equipment = Some(Equipment {
-            slot: EquipmentSlot::RightHand,
-            equipped: false,
-            vit_bonus: 0,
-            atk_bonus: 1,
-            str_bonus: 1,
-            def_bonus: 0,
-            dex_bonus: 0,
-            int_bonus: 0,
-            lck_bonus: 0
-        });
-
-        inventory.push(dagger);
+        map.generate(&mut objects, 1, &content, &mut rng);
 
         // Return newly created game
-        Game
+        let mut game = Game
         {
             map: map,
             objects: objects,
-            inventory: inventory,
             log: vec![],
             dungeon_level: 1,
+            turns: 0,
+            mode: GameMode::Permadeath,
+            kills: vec![],
+            death_cause: None,
+            auto_pickup: false,
+            seed: seed,
+            rng: rng,
+            actions: vec![],
+            replayable: true,
+            content: content,
+            slot: 0,
+            autosave_due: false,
+            activity: None,
+        };
+        game.log.add("You awaken in a dark dungeon...", colors::RED);
+        game.update_fov();
+
+        game
+    }
+
+    /// Feeds a single player action into the game and advances it. This is the
+    /// renderer-free core of the game: every action and menu choice comes in as
+    /// plain data, so it can be driven without a window (scripts, tests, ...).
+    pub fn step(&mut self, action: PlayerAction) -> StepOutcome
+    {
+        self.actions.push(action);
+
+        if !self.objects[PLAYER_ID].alive
+        {
+            return StepOutcome::PlayerDead;
+        }
+
+        // A pending level up has to be resolved before anything else can happen
+        if self.level_up_pending()
+        {
+            return match action
+            {
+                PlayerAction::LevelUp(stat) =>
+                {
+                    player_level_up(stat, self);
+                    self.outcome(StepOutcome::NoTurn)
+                }
+
+                _ => StepOutcome::LevelUp
+            };
+        }
+
+        let valid = match action
+        {
+            PlayerAction::Move(dx, dy) => dx.abs() <= 1 && dy.abs() <= 1,
+            PlayerAction::UseItem(inv_id) | PlayerAction::UseItemAt(inv_id, _) | PlayerAction::DropItem(inv_id) => inv_id < self.objects[PLAYER_ID].inventory.len(),
+            PlayerAction::Wait | PlayerAction::Interact => true,
+
+            // Level ups are only accepted while one is pending
+            PlayerAction::LevelUp(_) => false
+        };
+        if !valid
+        {
+            return StepOutcome::Invalid;
+        }
+
+        // Whether the action was carried out, rather than cancelled. Stunned
+        // players can't do anything but wait.
+        let acted = match action
+        {
+            PlayerAction::Wait => true,
+
+            _ if self.player_has_effect(StatusKind::Stun) => { self.log.add("You are stunned!", colors::RED); false },
+
+            PlayerAction::Move(dx, dy) => { player_move(dx, dy, self); true },
+
+            PlayerAction::Interact => player_interact(self),
+
+            PlayerAction::UseItem(inv_id) => item::use_item(inv_id, None, self),
+
+            PlayerAction::UseItemAt(inv_id, target) => item::use_item(inv_id, Some(target), self),
+
+            PlayerAction::DropItem(inv_id) => item::drop_item(inv_id, self),
+
+            PlayerAction::LevelUp(_) => false
+        };
+
+        // Update FOV so the AI reacts to where the player is now
+        self.update_fov();
+
+        // Actions that cost energy use up the player's turn, so let time pass 
+        // until the player is able to act again
+        let cost = if acted { action.energy_cost() } else { 0 };
+        let took_turn = cost > 0;
+        if took_turn
+        {
+            if let Some(fighter) = self.objects[PLAYER_ID].fighter.as_mut()
+            {
+                fighter.energy -= cost;
+            }
+            self.turns += 1;
+            if self.turns % AUTOSAVE_INTERVAL == 0
+            {
+                self.autosave_due = true;
+            }
+
+            pass_turn(self);
+            run_until_player_turn(self);
+        }
+
+        drop_loot(self);
+
+        self.outcome(if took_turn { StepOutcome::TookTurn } else { StepOutcome::NoTurn })
+    }
+
+    /// Rebuilds everything that isn't saved along with the game (FOV, the 
+    /// object store's free list and the content), then checks that the loaded
+    /// game actually makes sense
+    pub fn restore(&mut self, content: Content) -> Result< (), String >
+    {
+        self.validate()?;
+
+        self.content = content;
+        self.objects.rebuild_free_list();
+        self.map.generate_fov_map();
+        self.update_fov();
+
+        Ok(())
+    }
+
+    /// Checks the game for anything that would make it panic later on
+    fn validate(&self) -> Result< (), String >
+    {
+        let map = &self.map;
+        if map.width <= 0 || map.height <= 0 || map.tiles.len() != map.width as usize || map.tiles.iter().any(|col| col.len() != map.height as usize)
+        {
+            return Err("the map's tiles don't match its size".into());
+        }
+
+        match self.objects.get(PLAYER_ID)
+        {
+            Some(player) if player.fighter.is_some() => {},
+            Some(_) => return Err("the player isn't a fighter".into()),
+            None => return Err("there is no player".into())
+        }
+
+        if let Some(object) = self.objects.values().find(|o| !map.in_bounds(o.pos))
+        {
+            return Err(format!("{} is outside of the map at {:?}", object.name, object.pos));
+        }
+
+        if self.dungeon_level < 1
+        {
+            return Err(format!("invalid dungeon level {}", self.dungeon_level));
+        }
+
+        if !self.objects[PLAYER_ID].alive
+        {
+            return Err("the character in this save is dead".into());
+        }
+
+        Ok(())
+    }
+
+    /// Keeps track of the player's kills and of what killed the player. Called
+    /// whenever something is killed, with the victim's name from before it died
+    pub fn record_death(&mut self, killer: ObjectId, victim: ObjectId, victim_name: String)
+    {
+        if victim == PLAYER_ID && killer == PLAYER_ID
+        {
+            self.death_cause = Some(format!("Killed by their own magic on dungeon level {}", self.dungeon_level));
+        }
+        else if victim == PLAYER_ID
+        {
+            let killer_name = self.objects.get(killer).map_or("something".to_string(), |o| with_article(&o.name));
+            self.death_cause = Some(format!("Killed by {} on dungeon level {}", killer_name, self.dungeon_level));
+        }
+        else if killer == PLAYER_ID
+        {
+            self.kills.push(victim_name);
+        }
+    }
+
+    /// Returns how many of each kind of monster the player has killed, most killed first
+    pub fn kill_summary(&self) -> Vec< (String, usize) >
+    {
+        let mut summary: Vec< (String, usize) > = vec![];
+        for name in &self.kills
+        {
+            match summary.iter_mut().find(|(n, _)| n == name)
+            {
+                Some(entry) => entry.1 += 1,
+                None => summary.push((name.clone(), 1))
+            }
+        }
+        summary.sort_by_key(|&(_, count)| Reverse(count));
+
+        summary
+    }
+
+    /// Returns true if the player has enough experience to level up. While a
+    /// level up is pending the only accepted action is `PlayerAction::LevelUp`
+    pub fn level_up_pending(&self) -> bool
+    {
+        let player = &self.objects[PLAYER_ID];
+        let level_xp = LEVEL_UP_BASE + player.level * LEVEL_UP_FACTOR;
+
+        player.alive && player.fighter.as_ref().map_or(false, |f| f.xp >= level_xp)
+    }
+
+    /// Recomputes the player's FOV (and marks newly seen tiles as explored)
+    pub fn update_fov(&mut self)
+    {
+        let radius = if self.player_has_effect(StatusKind::Blindness) { BLIND_FOV_RADIUS } else { FOV_RADIUS };
+        self.map.recompute_fov(self.objects[PLAYER_ID].pos, radius);
+    }
+
+    /// Returns true if the player is under the given status effect
+    pub fn player_has_effect(&self, kind: StatusKind) -> bool
+    {
+        self.objects[PLAYER_ID].fighter.as_ref().map_or(false, |f| f.has_effect(kind))
+    }
+
+    /// Picks the outcome to report once an action has been carried out
+    fn outcome(&self, default: StepOutcome) -> StepOutcome
+    {
+        if !self.objects[PLAYER_ID].alive
+        {
+            StepOutcome::PlayerDead
+        }
+        else if self.level_up_pending()
+        {
+            StepOutcome::LevelUp
+        }
+        else
+        {
+            default
         }
     }
 
     /// Starts the game itself
     pub fn start(&mut self, tcod: &mut TCOD)
     {
-        let mut prev_player_pos = (-1, -1);
-        let mut key = Default::default();
-        self.log.add("You awaken in a dark dungeon...", colors::RED);
+        self.update_fov();
+
+        // Leave the last replay alone rather than overwrite it with one that
+        // can't be played back
+        let mut recorder = if !self.replayable
+        {
+            None
+        }
+        else
+        {
+            match ReplayRecorder::create(self)
+            {
+                Ok(recorder) => Some(recorder),
+                Err(e) =>
+                {
+                    self.log.add(format!("Failed to record the replay: {}", e), colors::RED);
+                    None
+                }
+            }
+        };
 
         while !tcod.root.window_closed()
         {
             // Check for tcod input events
+            let mut key: Key = Default::default();
+            let mut clicked = false;
             match tcod::input::check_for_event(tcod::input::MOUSE | tcod::input::KEY_PRESS)
             {
-                Some((_, Event::Mouse(m))) => tcod.mouse = m,
+                Some((_, Event::Mouse(m))) =>
+                {
+                    tcod.mouse = m;
+                    clicked = m.lbutton_pressed;
+                },
                 Some((_, Event::Key(k))) => key = k,
-                _ => key = Default::default()
+                _ => {}
             }
 
-            // Recompute the FOV map if the player position has updated
-            let fov_recompute = prev_player_pos != self.objects[PLAYER_ID].pos;
-
             // Render
-            self.render(tcod, fov_recompute);
+            self.render(tcod);
+            tcod.root.flush();
+
+            // Resolve a pending player level up before accepting any other input
+            if self.level_up_pending()
+            {
+                let fighter = self.objects[PLAYER_ID].fighter.as_ref().unwrap();
+                let stat = menu::level_up_menu(fighter, "Choose a stat to increase:", &mut tcod.root);
+                self.step(PlayerAction::LevelUp(stat));
+                self.record_replay(&mut recorder);
+                continue;
+            }
 
-            // Update player
-            player_level_up(tcod, self);
-            prev_player_pos = self.objects[PLAYER_ID].pos;
-            let player_action = self.handle_key_input(tcod, key);
-            if player_action == PlayerAction::Exit
+            // Keep the player's activity going, any key press or click stops it
+            let input = if self.activity.is_some() && key.code == KeyCode::NoKey && !clicked
             {
-                self.save_game().unwrap();
-                break;
+                activity::next_action(self).map_or(Input::None, Input::Action)
             }
+            else
+            {
+                self.activity = None;
+                if clicked
+                {
+                    self.handle_click(tcod);
+                    Input::None
+                }
+                else
+                {
+                    self.handle_key_input(tcod, key)
+                }
+            };
 
-            // Update AI
-            if self.objects[PLAYER_ID].alive && player_action != PlayerAction::NoAction
+            // Update player & AI
+            match input
             {
-                for id in 0..self.objects.len()
+                Input::Exit =>
                 {
-                    if self.objects[id].ai.is_some()
+                    if let Err(e) = save::save_game(self)
+                    {
+                        menu::msg_box(&format!("Failed to save the game!\n\n{}", e), menu::MSG_BOX_WIDTH, &mut tcod.root);
+                        continue;
+                    }
+
+                    // The game is saved by now, so failing to write the morgue
+                    // file is only worth a mention
+                    if let Err(e) = morgue::write_morgue(self, RunEnd::Quit)
                     {
-                        ai_take_turn(id, self);
+                        menu::msg_box(&format!("Failed to write the morgue file!\n\n{}", e), menu::MSG_BOX_WIDTH, &mut tcod.root);
                     }
+                    break;
+                }
+
+                Input::Action(action) =>
+                {
+                    let outcome = self.step(action);
+                    self.record_replay(&mut recorder);
+                    if outcome == StepOutcome::PlayerDead
+                    {
+                        self.game_over(tcod);
+                        break;
+                    }
+                },
+
+                Input::None => {}
+            }
+
+            // Autosave whenever the game asks for it
+            if self.autosave_due
+            {
+                self.autosave_due = false;
+                if let Err(e) = save::save_game(self)
+                {
+                    self.log.add(format!("Autosave failed: {}", e), colors::RED);
                 }
             }
         }
     }
 
-    /// Renders the game
-    fn render(&mut self, tcod: &mut TCOD, fov_recompute: bool)
+    /// Appends the actions taken since the last call to the replay file. If
+    /// that fails the rest of the run isn't recorded.
+    fn record_replay(&mut self, recorder: &mut Option< ReplayRecorder >)
     {
-        tcod.root.clear();
-        tcod.con.clear();
+        if let Some(Err(e)) = recorder.as_mut().map(|r| r.record(self))
+        {
+            self.log.add(format!("Failed to record the replay: {}", e), colors::RED);
+            *recorder = None;
+        }
+    }
+
+    /// Ends the game after the player died: shows the death screen and, unless
+    /// the game is casual, deletes the save so the character can't be continued
+    fn game_over(&mut self, tcod: &mut TCOD)
+    {
+        self.render(tcod);
+        tcod.root.flush();
+
+        if self.mode == GameMode::Permadeath
+        {
+            match save::delete_slot(self.slot)
+            {
+                Ok(()) | Err(SaveError::Missing) => {},
+                Err(e) => self.log.add(format!("Failed to delete the save: {}", e), colors::RED)
+            }
+        }
 
-        // Recompute FOV if necessary
-        if fov_recompute
+        if let Err(e) = morgue::write_morgue(self, RunEnd::Death)
         {
-            self.map.recompute_fov(self.objects[PLAYER_ID].pos);
+            self.log.add(format!("Failed to write the morgue file: {}", e), colors::RED);
         }
 
+        menu::death_screen(self, &mut tcod.root);
+    }
+
+    /// Renders the game. The root console still has to be flushed afterwards
+    pub fn render(&mut self, tcod: &mut TCOD)
+    {
+        tcod.root.clear();
+        tcod.con.clear();
+
         // Render map
         self.map.draw(&mut tcod.con);
 
         // Render objects
-        let mut to_draw: Vec< _ > = self.objects.iter().filter(|o| { self.map.is_in_fov(o.pos) || (o.always_visible && self.map.is_explored(o.pos)) }).collect();
+        let mut to_draw: Vec< _ > = self.objects.values().filter(|o| { self.map.is_in_fov(o.pos) || (o.always_visible && self.map.is_explored(o.pos)) }).collect();
         to_draw.sort_by(|o1, o2| o1.solid.cmp(&o2.solid));
         for obj in &to_draw
         {
@@ -165,155 +545,283 @@ impl Game
 
         // Render gui
         gui::render_gui(tcod, self);
+    }
 
-        tcod.root.flush();
+    /// Handles a left click on the map by travelling to the clicked tile
+    fn handle_click(&mut self, tcod: &mut TCOD)
+    {
+        let pos = (tcod.mouse.cx as i32, tcod.mouse.cy as i32);
+        if self.map.in_bounds(pos) && self.map.is_explored(pos) && !self.map.tiles[pos.0 as usize][pos.1 as usize].blocked
+        {
+            activity::start_activity(Activity::Travel(pos), self);
+        }
     }
 
-    /// Handles player keyboard input and game controls
-    fn handle_key_input(&mut self, tcod: &mut TCOD, key: Key) -> PlayerAction
+    /// Returns the action for using the item at the given inventory index,
+    /// letting the player pick a target first if it has to be aimed. Returns
+    /// None if the player cancelled targeting.
+    fn aim_item(&mut self, inv_id: usize, tcod: &mut TCOD) -> Option< PlayerAction >
     {
-        let player_alive = self.objects[PLAYER_ID].alive;
-        match (key, player_alive)
+        let spec = self.objects[PLAYER_ID].inventory[inv_id].item.and_then(|i| i.targeting());
+        match spec
         {
-            // Escape to exit game
-            (Key { code: KeyCode::Escape, .. }, _) => PlayerAction::Exit,
+            Some(spec) => targeting::select_target(self, &spec, tcod).map(|target| PlayerAction::UseItemAt(inv_id, target)),
+            None => Some(PlayerAction::UseItem(inv_id))
+        }
+    }
 
-            // Up to move player upwards
-            (Key { code: KeyCode::Up, .. }, true) => { player_take_turn(0, -1, self); PlayerAction::Action },
+    /// Handles player keyboard input and game controls, translating key presses
+    /// (and any menu choices they open) into player actions
+    fn handle_key_input(&mut self, tcod: &mut TCOD, key: Key) -> Input
+    {
+        let player_alive = self.objects[PLAYER_ID].alive;
+        let command = match tcod.keymap.command(key)
+        {
+            Some(command) => command,
+            None => return Input::None
+        };
 
-            // Down to move player downwards
-            (Key { code: KeyCode::Down, .. }, true) => { player_take_turn(0, 1, self); PlayerAction::Action },
+        match (command, player_alive)
+        {
+            // Exit the game
+            (Command::Exit, _) => Input::Exit,
 
-            // Left to move player left
-            (Key { code: KeyCode::Left, .. }, true) => { player_take_turn(-1, 0, self); PlayerAction::Action },
+            // Show the key bindings
+            (Command::Help, _) =>
+            {
+                menu::help_menu(&tcod.keymap, &mut tcod.root);
+                Input::None
+            },
 
-            // Right to move player right
-            (Key { code: KeyCode::Right, .. }, true) => { player_take_turn(1, 0, self); PlayerAction::Action },
+            // Move or attack in a direction
+            (_, true) if command.direction().is_some() =>
+            {
+                let (dx, dy) = command.direction().unwrap();
+                Input::Action(PlayerAction::Move(dx, dy))
+            },
 
-            // R to skip player turn
-            (Key { printable: 'r', .. }, true) => PlayerAction::Action,
+            // Skip the player's turn
+            (Command::Wait, true) => Input::Action(PlayerAction::Wait),
 
-            // F to interact with item or non-monster object with
-            (Key { printable: 'f', .. }, true) => 
+            // Rest until healed or something interesting happens
+            (Command::Rest, true) =>
             {
-                // first check if the object is an item
-                let item_id = self.objects.iter().position(|o| o.pos == self.objects[PLAYER_ID].pos && o.item.is_some());
-                if let Some(item_id) = item_id
-                {
-                    item::pick_item_up(item_id, self);
-                    return PlayerAction::NoAction;
-                }
+                activity::start_activity(Activity::Rest(REST_TURN_LIMIT), self);
+                Input::None
+            },
 
-                // Next check if it's stairs
-                let player_on_stairs = self.objects.iter().any(|o| { o.pos == self.objects[PLAYER_ID].pos && o.name == "Stairs" });
-                if player_on_stairs
-                {
-                    advance_dungeon_level(self);
-                    return PlayerAction::NoAction;
-                }
+            // Explore the level until something interesting happens
+            (Command::Explore, true) =>
+            {
+                activity::start_activity(Activity::Explore, self);
+                Input::None
+            },
 
-                PlayerAction::NoAction 
+            // Turn picking up items while exploring on or off
+            (Command::ToggleAutoPickup, true) =>
+            {
+                self.auto_pickup = !self.auto_pickup;
+                let state = if self.auto_pickup { "on" } else { "off" };
+                self.log.add(format!("Auto-pickup is now {}.", state), colors::WHITE);
+                Input::None
             },
 
-            // I to open inventory
-            (Key { printable: 'i', .. }, true) => 
+            // Interact with an item or non-monster object
+            (Command::Interact, true) => Input::Action(PlayerAction::Interact),
+
+            // Open the inventory
+            (Command::Inventory, true) => 
             {
-                let inv_index = menu::inventory_menu(&self.inventory, "Press the key next to an item to use it, or any other to cancel.\n", &mut tcod.root);
-                if let Some(inv_index) = inv_index
+                let inv_index = menu::inventory_menu(&self.objects[PLAYER_ID].inventory, "Press the key next to an item to use it, or any other to cancel.\n", &mut tcod.root);
+                match inv_index
                 {
-                    item::use_item(inv_index, self, tcod);
+                    Some(i) => self.aim_item(i, tcod).map_or(Input::None, Input::Action),
+                    None => Input::None
                 }
+            },
 
-                PlayerAction::NoAction 
+            // Open the inventory in drop mode
+            (Command::Drop, true) =>
+            {
+                let inv_index = menu::inventory_menu(&self.objects[PLAYER_ID].inventory, "Press the key next to an item to drop it, or any other key to cancel.\n", &mut tcod.root);
+                inv_index.map_or(Input::None, |i| Input::Action(PlayerAction::DropItem(i)))
             },
 
-            // O to open inventory in drop mode
-            (Key { printable: 'o', .. }, true) =>
+            // Look around the map with a cursor
+            (Command::Look, true) =>
             {
-                let inv_index = menu::inventory_menu(&self.inventory, "Press the key next to an item to drop it, or any other key to cancel.\n", &mut tcod.root);
-                if let Some(inv_index) = inv_index
-                {
-                    item::drop_item(inv_index, self);
-                }
-                PlayerAction::NoAction
+                look::look_mode(self, tcod);
+                Input::None
             },
 
-            // C to open character info 
-            (Key { printable: 'c', .. }, true) => 
+            // Open the character info
+            (Command::Character, true) =>
             { 
                 menu::character_menu(self, &mut tcod.root);
-                PlayerAction::NoAction 
+                Input::None
             },
 
-            // No other controls at this time...
-            _ => PlayerAction::NoAction
+            // Nothing else can be done while dead
+            _ => Input::None
         }
     }
+}
 
-    /// Serialize the current game state into a save file using Serde Json
-    fn save_game(&mut self) -> Result< (), Box< Error > >
+/// Represents what happens to the save when the player dies
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameMode
+{
+    /// The save is deleted when the player dies
+    Permadeath,
+
+    /// The save is kept when the player dies, so the game can be continued
+    /// from the last time it was saved
+    Casual
+}
+
+impl Default for GameMode
+{
+    /// Games saved before there were modes keep their save
+    fn default() -> Self { GameMode::Casual }
+}
+
+/// Games saved before there was a `replayable` flag were all created from their seed
+fn default_replayable() -> bool
+{
+    true
+}
+
+/// Returns the given name with an "a" or "an" in front of it
+fn with_article(name: &str) -> String
+{
+    match name.chars().next()
     {
-        let save_data = serde_json::to_string(&(self))?;
-        let mut file = File::create("savegame")?;
-        file.write_all(save_data.as_bytes())?;
+        Some(c) if "AEIOUaeiou".contains(c) => format!("an {}", name),
+        _ => format!("a {}", name)
+    }
+}
 
-        Ok(())
+/// Represents an "action" the player can take. Actions (and the choices made
+/// in menus) are plain data so the game can run without a renderer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PlayerAction
+{
+    /// Move in the given direction, attacking anything in the way
+    Move(i32, i32),
+
+    /// Do nothing and let the monsters take their turn
+    Wait,
+
+    /// Pick up the item under the player or take the stairs
+    Interact,
+
+    /// Use the item at the given inventory index
+    UseItem(usize),
+
+    /// Use the item at the given inventory index, aimed at the given position
+    UseItemAt(usize, (i32, i32)),
+
+    /// Drop the item at the given inventory index
+    DropItem(usize),
+
+    /// Spend a pending level up on the given stat
+    LevelUp(Stat)
+}
+
+impl PlayerAction
+{
+    /// Returns the amount of energy this action costs when it's carried out.
+    /// Actions that don't cost any energy don't use up the player's turn.
+    pub fn energy_cost(self) -> i32
+    {
+        match self
+        {
+            PlayerAction::LevelUp(_) => 0,
+            _ => ACTION_COST
+        }
     }
 }
 
-/// Represents an "action" the player can take on their turn
+/// Represents the result of a single call to `Game::step`
 #[derive(Debug, Clone, Copy, PartialEq)]
-enum PlayerAction
+pub enum StepOutcome
+{
+    /// The player used up their turn and the monsters acted
+    TookTurn,
+
+    /// The action was carried out without using up a turn
+    NoTurn,
+
+    /// The action couldn't be carried out (e.g. a bad inventory index or a
+    /// move of more than one tile)
+    Invalid,
+
+    /// The player has to pick a stat with `PlayerAction::LevelUp` before anything else
+    LevelUp,
+
+    /// The player is dead; no more actions will be accepted
+    PlayerDead
+}
+
+/// Represents what the frontend should do in response to a key press
+enum Input
 {
     Exit,
-    Action,
-    NoAction
+    Action(PlayerAction),
+    None
 }
 
-/// Increases the player's level if necessary
-fn player_level_up(tcod: &mut TCOD, game: &mut Game)
+/// Increases the player's level, spending the level up on the given stat
+fn player_level_up(stat: Stat, game: &mut Game)
 {
     let player = &mut game.objects[PLAYER_ID];
     let level_xp = LEVEL_UP_BASE + player.level * LEVEL_UP_FACTOR;
 
-    if player.fighter.as_ref().map_or(0, |f| f.xp) >= level_xp
-    {
-        player.level += 1;
-        game.log.add(format!("Your battle skills grow stronger! You've reached level {}!", player.level), colors::YELLOW);
+    player.level += 1;
+    game.log.add(format!("Your battle skills grow stronger! You've reached level {}!", player.level), colors::YELLOW);
 
-        let fighter = player.fighter.as_mut().unwrap();
-        let stat = menu::level_up_menu(fighter, "Choose a stat to increase:", &mut tcod.root);
-        match stat.unwrap()
+    let fighter = player.fighter.as_mut().unwrap();
+    match stat
+    {
+        Stat::Vitality =>
         {
-            // Vitality
-            0 => {
-                fighter.base_vit += 1;
-                fighter.max_hp = 10 + (5 * fighter.base_vit);
-            },
+            fighter.base_vit += 1;
+            fighter.max_hp = 10 + (5 * fighter.base_vit);
+        },
 
-            // Attack
-            1 => fighter.base_atk += 1,
+        Stat::Attack => fighter.base_atk += 1,
 
-            // Strength
-            2 => fighter.base_str += 1,
+        Stat::Strength => fighter.base_str += 1,
 
-            // Defense
-            3 => fighter.base_def += 1,
+        Stat::Defense => fighter.base_def += 1,
 
-            // Dexterity
-            4 => fighter.base_dex += 1,
+        Stat::Dexterity => fighter.base_dex += 1,
 
-            // Intelligence
-            5 => fighter.base_int += 1,
+        Stat::Intelligence => fighter.base_int += 1,
 
-            // Luck
-            6 => fighter.base_lck += 1,
+        Stat::Luck => fighter.base_lck += 1
+    }
 
-            _ => unreachable!()
-        }
+    fighter.hp = fighter.max_hp;
+    fighter.xp -= level_xp;
+}
 
-        fighter.hp = fighter.max_hp;
-        fighter.xp -= level_xp;
+/// Moves the player, unless they're stunned. Confused players stumble in a
+/// random direction instead of the one they wanted.
+fn player_move(dx: i32, dy: i32, game: &mut Game)
+{
+    if game.player_has_effect(StatusKind::Confusion)
+    {
+        let dx = game.rng.gen_range(-1, 2);
+        let dy = game.rng.gen_range(-1, 2);
+        if (dx, dy) != (0, 0)
+        {
+            player_take_turn(dx, dy, game);
+        }
+    }
+    else
+    {
+        player_take_turn(dx, dy, game);
     }
 }
 
@@ -321,13 +829,23 @@ fn player_take_turn(dx: i32, dy: i32, game: &mut Game)
 {
     let x = game.objects[PLAYER_ID].pos.0 + dx;
     let y = game.objects[PLAYER_ID].pos.1 + dy;
-    let target_id = game.objects.iter().position(|o| o.fighter.is_some() && o.pos == (x, y));
+    if game.map.cuts_corner(game.objects[PLAYER_ID].pos, dx, dy)
+    {
+        return;
+    }
+
+    let target_id = game.objects.iter().find(|(_, o)| o.fighter.is_some() && o.pos == (x, y)).map(|(id, _)| id);
     match target_id
     {
         Some(target_id) =>
         {
-            let (player, target) = object::mut_two(PLAYER_ID, target_id, &mut game.objects);
-            player.attack(target, &game.inventory, &mut game.log);
+            let target_name = game.objects[target_id].name.clone();
+            let (player, target) = game.objects.mut_two(PLAYER_ID, target_id);
+            if player.attack(target, &mut game.log)
+            {
+                game.record_death(PLAYER_ID, target_id, target_name);
+            }
+            ai::make_noise((x, y), ai::COMBAT_NOISE, game);
         }
 
         None =>
@@ -337,14 +855,186 @@ fn player_take_turn(dx: i32, dy: i32, game: &mut Game)
     }
 }
 
-/// Called whenever it is the ai's "turn" (after the player took an action)
-fn ai_take_turn(id: usize, game: &mut Game)
+/// Interacts with whatever is under the player: picks up an item or takes the
+/// stairs. Returns false if there was nothing to do.
+fn player_interact(game: &mut Game) -> bool
+{
+    // First check if the object is an item
+    let player_pos = game.objects[PLAYER_ID].pos;
+    let item_id = game.objects.iter().find(|(_, o)| o.pos == player_pos && o.item.is_some()).map(|(id, _)| id);
+    if let Some(item_id) = item_id
+    {
+        return item::pick_item_up(item_id, game);
+    }
+
+    // Next check if it's stairs
+    let player_on_stairs = game.objects.values().any(|o| { o.pos == player_pos && o.name == "Stairs" });
+    if player_on_stairs
+    {
+        advance_dungeon_level(game);
+    }
+
+    player_on_stairs
+}
+
+/// Drops everything carried by dead monsters onto the floor where they died
+fn drop_loot(game: &mut Game)
+{
+    for id in game.objects.ids()
+    {
+        let monster = &mut game.objects[id];
+        if id == PLAYER_ID || monster.alive || monster.inventory.is_empty()
+        {
+            continue;
+        }
+
+        let pos = monster.pos;
+        let loot: Vec< Object > = monster.inventory.drain(..).collect();
+        for mut item in loot
+        {
+            if let Some(equipment) = item.equipment.as_mut()
+            {
+                equipment.equipped = false;
+            }
+
+            game.log.add(format!("The {} falls to the floor.", item.name), colors::YELLOW);
+            item.set_pos(pos.0, pos.1);
+            item.always_visible = true;
+            game.objects.insert(item);
+        }
+    }
+}
+
+/// Everything that happens once for every turn the player takes, however
+/// fast the player and the monsters are
+fn pass_turn(game: &mut Game)
+{
+    // Everything with a fighter regenerates
+    for id in game.objects.ids()
+    {
+        let vitality = game.objects[id].vitality_value();
+        if let Some(fighter) = game.objects[id].fighter.as_mut()
+        {
+            fighter.regenerate(vitality);
+        }
+    }
+
+    tick_status_effects(game);
+}
+
+/// Advances time until the player has built up enough energy to act again.
+/// Every tick each fighter gains energy equal to its speed, and monsters act 
+/// whenever they have enough energy saved up. This way fast monsters can act
+/// several times per player turn and slow ones only every few turns.
+fn run_until_player_turn(game: &mut Game)
 {
+    while game.objects[PLAYER_ID].alive && game.objects[PLAYER_ID].fighter.as_ref().map_or(false, |f| f.energy < ACTION_COST)
+    {
+        // Everything with a fighter gains energy
+        for id in game.objects.ids()
+        {
+            let speed = game.objects[id].speed_value(id == PLAYER_ID);
+            if let Some(fighter) = game.objects[id].fighter.as_mut()
+            {
+                fighter.energy += speed;
+            }
+        }
+
+        // Update AI
+        for id in game.objects.ids()
+        {
+            // Objects can be removed or killed by earlier turns, so check the handle is still live
+            while game.objects[PLAYER_ID].alive && game.objects.get(id).map_or(false, |o| o.ai.is_some() && o.fighter.as_ref().map_or(false, |f| f.energy >= ACTION_COST))
+            {
+                ai_take_turn(id, game);
+                if let Some(fighter) = game.objects.get_mut(id).and_then(|o| o.fighter.as_mut())
+                {
+                    fighter.energy -= ACTION_COST;
+                }
+            }
+        }
+    }
+}
+
+/// Applies every fighter's status effects for one of the player's turns, so an
+/// effect lasting N turns lasts N of the player's actions. Anything that dies
+/// of poison dies the same as if it was killed, with the player getting the
+/// experience for monsters.
+fn tick_status_effects(game: &mut Game)
+{
+    for id in game.objects.ids()
+    {
+        let expired = match game.objects[id].fighter.as_mut()
+        {
+            Some(fighter) if !fighter.effects.is_empty() => fighter.tick_effects(),
+            _ => continue
+        };
+
+        // Only mention effects wearing off on things the player can see
+        let object = &game.objects[id];
+        if id == PLAYER_ID || game.map.is_in_fov(object.pos)
+        {
+            for kind in expired
+            {
+                let msg = if id == PLAYER_ID
+                {
+                    format!("You are no longer {}.", kind.name().to_lowercase())
+                }
+                else
+                {
+                    format!("The {} is no longer {}.", object.name, kind.name().to_lowercase())
+                };
+                game.log.add(msg, colors::LIGHT_GREY);
+            }
+        }
+
+        let name = game.objects[id].name.clone();
+        if let Some(xp) = game.objects[id].take_damage(0, &mut game.log)
+        {
+            if id == PLAYER_ID
+            {
+                game.death_cause = Some(format!("Died of poison on dungeon level {}", game.dungeon_level));
+            }
+            else
+            {
+                if let Some(fighter) = game.objects[PLAYER_ID].fighter.as_mut()
+                {
+                    fighter.xp += xp;
+                }
+                game.record_death(PLAYER_ID, id, name);
+            }
+        }
+    }
+}
+
+/// Called whenever it is the ai's "turn" (after the player took an action).
+/// Stunned monsters lose their turn and confused ones stumble around instead
+/// of following their AI.
+fn ai_take_turn(id: ObjectId, game: &mut Game)
+{
+    let (stunned, confused) = game.objects[id].fighter.as_ref().map_or((false, false), |f| {
+        (f.has_effect(StatusKind::Stun), f.has_effect(StatusKind::Confusion))
+    });
+    if stunned
+    {
+        return;
+    }
+    if confused
+    {
+        ai::ai_confused(id, game);
+        return;
+    }
+
     if let Some(ai) = game.objects[id].ai.take()
     {
         let new_ai = match ai
         {
             Ai::BasicMonster => ai::ai_basic_monster(id, game),
+            Ai::Sleeping { awake } => ai::ai_sleeping(id, awake, game),
+            Ai::Wanderer { goal } => ai::ai_wanderer(id, goal, game),
+            Ai::Ranged { range } => ai::ai_ranged(id, range, game),
+            Ai::Coward { flee_below, fleeing } => ai::ai_coward(id, flee_below, fleeing, game),
+            Ai::PackHunter { pack, target } => ai::ai_pack_hunter(id, pack, target, game),
         };
 
         game.objects[id].ai = Some(new_ai);
@@ -356,11 +1046,199 @@ fn advance_dungeon_level(game: &mut Game)
 {
     // Heal player up by half their max hp
     game.log.add("You take a moment to rest and recover your strength.", colors::GREEN);
-    let heal_amt = game.objects[PLAYER_ID].fighter.map_or(0, |f| f.max_hp / 2);
+    let heal_amt = game.objects[PLAYER_ID].fighter.as_ref().map_or(0, |f| f.max_hp / 2);
     game.objects[PLAYER_ID].heal(heal_amt);
 
     // Create the new dungeon level
     game.log.add("You descend deeper into the heart of the dungeon...", colors::RED);
     game.dungeon_level += 1;
-    game.map.generate(&mut game.objects, game.dungeon_level);
-}
\ No newline at end of file
+    game.map.generate(&mut game.objects, game.dungeon_level, &game.content, &mut game.rng);
+    game.autosave_due = true;
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::content::load_content;
+    use crate::path::step_distance;
+
+    const SEED: u64 = 42;
+
+    fn new_game() -> Game
+    {
+        Game::new(SEED, load_content().expect("Failed to load content!"))
+    }
+
+    #[test]
+    fn same_seed_and_actions_give_the_same_game()
+    {
+        let actions = [
+            PlayerAction::Move(1, 0),
+            PlayerAction::Wait,
+            PlayerAction::Move(0, 1),
+            PlayerAction::Move(-1, -1),
+            PlayerAction::Interact,
+            PlayerAction::Wait
+        ];
+
+        let (mut first, mut second) = (new_game(), new_game());
+        for &action in actions.iter()
+        {
+            assert_eq!(first.step(action), second.step(action));
+        }
+
+        assert_eq!(first.objects[PLAYER_ID].pos, second.objects[PLAYER_ID].pos);
+        assert_eq!(first.turns, second.turns);
+        assert_eq!(first.log, second.log);
+        assert_eq!(first.actions, second.actions);
+    }
+
+    #[test]
+    fn moves_of_more_than_one_tile_are_invalid()
+    {
+        let mut game = new_game();
+        let pos = game.objects[PLAYER_ID].pos;
+        for &(dx, dy) in [(5, 0), (0, -2), (-100, 100)].iter()
+        {
+            assert_eq!(game.step(PlayerAction::Move(dx, dy)), StepOutcome::Invalid);
+            assert_eq!(game.objects[PLAYER_ID].pos, pos);
+        }
+        assert_eq!(game.turns, 0);
+    }
+
+    #[test]
+    fn items_with_bad_inventory_indices_are_invalid()
+    {
+        let mut game = new_game();
+        let count = game.objects[PLAYER_ID].inventory.len();
+        assert_eq!(game.step(PlayerAction::UseItem(count)), StepOutcome::Invalid);
+        assert_eq!(game.step(PlayerAction::UseItemAt(count, (0, 0))), StepOutcome::Invalid);
+        assert_eq!(game.step(PlayerAction::DropItem(count + 10)), StepOutcome::Invalid);
+        assert_eq!(game.objects[PLAYER_ID].inventory.len(), count);
+        assert_eq!(game.turns, 0);
+    }
+
+    #[test]
+    fn using_an_item_uses_it_up()
+    {
+        let mut game = new_game();
+        let potion = game.content.item("Health Potion").expect("Missing health potion!").spawn(0, 0);
+        game.objects[PLAYER_ID].inventory.push(potion);
+        game.objects[PLAYER_ID].fighter.as_mut().unwrap().hp = 1;
+
+        let inv_id = game.objects[PLAYER_ID].inventory.len() - 1;
+        assert_eq!(game.step(PlayerAction::UseItem(inv_id)), StepOutcome::TookTurn);
+        assert_eq!(game.objects[PLAYER_ID].inventory.len(), inv_id);
+        assert!(game.objects[PLAYER_ID].fighter.as_ref().unwrap().hp > 1);
+    }
+
+    #[test]
+    fn cancelled_actions_take_no_turn()
+    {
+        let mut game = new_game();
+        let potion = game.content.item("Health Potion").expect("Missing health potion!").spawn(0, 0);
+        game.objects[PLAYER_ID].inventory.push(potion);
+
+        // The player is at full health and there's nothing to pick up at the start
+        let inv_id = game.objects[PLAYER_ID].inventory.len() - 1;
+        assert_eq!(game.step(PlayerAction::UseItem(inv_id)), StepOutcome::NoTurn);
+        assert_eq!(game.step(PlayerAction::Interact), StepOutcome::NoTurn);
+        assert_eq!(game.objects[PLAYER_ID].inventory.len(), inv_id + 1);
+        assert_eq!(game.turns, 0);
+    }
+
+    #[test]
+    fn dropping_an_item_leaves_it_under_the_player()
+    {
+        let mut game = new_game();
+        let pos = game.objects[PLAYER_ID].pos;
+        let name = game.objects[PLAYER_ID].inventory[0].name.clone();
+
+        assert_eq!(game.step(PlayerAction::DropItem(0)), StepOutcome::TookTurn);
+        assert!(game.objects[PLAYER_ID].inventory.iter().all(|item| item.name != name));
+        assert!(game.objects.values().any(|o| o.name == name && o.pos == pos));
+    }
+
+    #[test]
+    fn a_pending_level_up_blocks_other_actions()
+    {
+        let mut game = new_game();
+        game.objects[PLAYER_ID].fighter.as_mut().unwrap().xp = LEVEL_UP_BASE + LEVEL_UP_FACTOR;
+        let pos = game.objects[PLAYER_ID].pos;
+        let strength = game.objects[PLAYER_ID].fighter.as_ref().unwrap().base_str;
+
+        assert_eq!(game.step(PlayerAction::Wait), StepOutcome::LevelUp);
+        assert_eq!(game.step(PlayerAction::Move(1, 0)), StepOutcome::LevelUp);
+        assert_eq!(game.objects[PLAYER_ID].pos, pos);
+        assert_eq!(game.turns, 0);
+
+        assert_eq!(game.step(PlayerAction::LevelUp(Stat::Strength)), StepOutcome::NoTurn);
+        assert_eq!(game.objects[PLAYER_ID].level, 2);
+        assert_eq!(game.objects[PLAYER_ID].fighter.as_ref().unwrap().base_str, strength + 1);
+    }
+
+    #[test]
+    fn a_dead_player_takes_no_more_actions()
+    {
+        let mut game = new_game();
+        game.objects[PLAYER_ID].alive = false;
+        let pos = game.objects[PLAYER_ID].pos;
+
+        assert_eq!(game.step(PlayerAction::Wait), StepOutcome::PlayerDead);
+        assert_eq!(game.step(PlayerAction::Move(1, 0)), StepOutcome::PlayerDead);
+        assert_eq!(game.objects[PLAYER_ID].pos, pos);
+        assert_eq!(game.turns, 0);
+    }
+
+    #[test]
+    fn reading_a_scroll_lets_the_monsters_act()
+    {
+        let mut game = new_game();
+        game.map = Map::new();
+        game.objects.retain(|id, _| id == PLAYER_ID);
+        game.objects[PLAYER_ID].pos = (10, 10);
+
+        let template = game.content.monsters.iter().find(|m| m.name == "Orc").expect("Missing orc!");
+        let mut orc = template.spawn(13, 10);
+        orc.ai = Some(Ai::BasicMonster);
+        orc.fighter.as_mut().unwrap().hp = 1000;
+        let orc = game.objects.insert(orc);
+
+        let scroll = game.content.item("Scroll of Lightning").expect("Missing lightning scroll!").spawn(0, 0);
+        game.objects[PLAYER_ID].inventory.push(scroll);
+        game.update_fov();
+
+        let inv_id = game.objects[PLAYER_ID].inventory.len() - 1;
+        assert_eq!(game.step(PlayerAction::UseItem(inv_id)), StepOutcome::TookTurn);
+        assert!(game.objects[orc].fighter.as_ref().unwrap().hp < 1000);
+        assert_eq!(step_distance(game.objects[orc].pos, (10, 10)), 2);
+        assert_eq!(game.turns, 1);
+    }
+
+    #[test]
+    fn stunned_players_can_only_wait()
+    {
+        let mut game = new_game();
+        let potion = game.content.item("Health Potion").expect("Missing health potion!").spawn(0, 0);
+        game.objects[PLAYER_ID].inventory.push(potion);
+        game.objects[PLAYER_ID].fighter.as_mut().unwrap().hp = 1;
+        game.objects[PLAYER_ID].fighter.as_mut().unwrap().add_effect(StatusKind::Stun, 1);
+        let pos = game.objects[PLAYER_ID].pos;
+        let inv_id = game.objects[PLAYER_ID].inventory.len() - 1;
+
+        for &action in [PlayerAction::Move(1, 0), PlayerAction::Interact, PlayerAction::UseItem(inv_id), PlayerAction::DropItem(0)].iter()
+        {
+            assert_eq!(game.step(action), StepOutcome::NoTurn);
+        }
+        assert_eq!(game.step(PlayerAction::Move(2, 0)), StepOutcome::Invalid);
+        assert_eq!(game.objects[PLAYER_ID].pos, pos);
+        assert_eq!(game.objects[PLAYER_ID].inventory.len(), inv_id + 1);
+        assert_eq!(game.turns, 0);
+
+        // Waiting lets the stun wear off
+        assert_eq!(game.step(PlayerAction::Wait), StepOutcome::TookTurn);
+        assert!(!game.player_has_effect(StatusKind::Stun));
+        assert_eq!(game.step(PlayerAction::UseItem(inv_id)), StepOutcome::TookTurn);
+    }
+}
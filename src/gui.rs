@@ -48,9 +48,9 @@ pub fn render_gui(tcod: &mut TCOD, game: &mut Game)
     }
 
     // Get player stats
-    let hp = game.objects[PLAYER_ID].fighter.map_or(0, |f| f.hp);
-    let max_hp = game.objects[PLAYER_ID].fighter.map_or(0, |f| f.max_hp);
-    let xp = game.objects[PLAYER_ID].fighter.map_or(0, |f| f.xp);
+    let hp = game.objects[PLAYER_ID].fighter.as_ref().map_or(0, |f| f.hp);
+    let max_hp = game.objects[PLAYER_ID].fighter.as_ref().map_or(0, |f| f.max_hp);
+    let xp = game.objects[PLAYER_ID].fighter.as_ref().map_or(0, |f| f.xp);
     let xp_target = LEVEL_UP_BASE + game.objects[PLAYER_ID].level * LEVEL_UP_FACTOR;
 
     // Reset foreground color to white
@@ -63,6 +63,15 @@ pub fn render_gui(tcod: &mut TCOD, game: &mut Game)
     tcod.gui.print_ex(1, 4, BackgroundFlag::None, TextAlignment::Left, "XP:");
     render_progress_bar(&mut tcod.gui, 4, 4, BAR_WIDTH, xp, xp_target, colors::LIGHT_BLUE, colors::BLACK);
 
+    // Render the player's status effects with the turns they have left
+    let effects: Vec< String > = game.objects[PLAYER_ID].fighter
+        .as_ref()
+        .map_or(vec![], |f| f.effects.iter().map(|e| format!("{} ({})", e.kind.name(), e.turns)).collect());
+    tcod.gui.set_default_foreground(colors::LIGHT_YELLOW);
+    tcod.gui.print_rect(1, 5, BAR_WIDTH + 4, 3, effects.join(", "));
+    tcod.gui.set_default_foreground(colors::WHITE);
+
+    tcod.gui.print_ex(1, 8, BackgroundFlag::None, TextAlignment::Left, format!("Seed: {}", game.seed));
     tcod.gui.print_ex(1, 9, BackgroundFlag::None, TextAlignment::Left, format!("Dungeon Level: {}", game.dungeon_level));
 
     // Display names of objects under mouse
@@ -99,7 +108,7 @@ fn get_names_under_mouse(mouse: Mouse, game: &Game) -> String
 {
     let (x, y) = (mouse.cx as i32, mouse.cy as i32);
     let names = game.objects
-        .iter()
+        .values()
         .filter(|o| o.pos == (x, y) && game.map.is_in_fov(o.pos))
         .map(|o| o.name.clone())
         .collect::< Vec< _ > >();
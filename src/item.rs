@@ -1,19 +1,54 @@
-use crate::TCOD;
 use crate::game::{ Game, PLAYER_ID };
 use crate::object::Object;
+use crate::entity::ObjectId;
 use crate::gui::MessageLog;
+use crate::targeting::{ self, TargetSpec };
+use crate::fighter::StatusKind;
+use crate::ai;
 
 use tcod::colors;
 
-pub const HEALTH_POTION_HEAL_AMT: i32 = 5;
+/// The most items anything can carry, one for every letter in the inventory menu
+pub const MAX_INVENTORY: usize = 26;
 
+/// Represents what happens when an item is used
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Item
 {
-    HealthPotion,
-    Sword,
-    Shield,
-    PlateArmor
+    /// Heals the player by the given amount
+    Heal { amount: i32 },
+
+    /// Equips or unequips the item
+    Equip,
+
+    /// Strikes the closest monster in range for the given damage plus the
+    /// reader's intelligence
+    Lightning { damage: i32, range: i32 },
+
+    /// Explodes on a chosen tile, hitting every fighter within the radius (the
+    /// reader included) for the given damage plus the reader's intelligence
+    Fireball { damage: i32, radius: i32, range: i32 },
+
+    /// Makes a chosen monster move randomly for the given number of turns plus
+    /// half of the reader's intelligence
+    Confusion { turns: i32, range: i32 },
+
+    /// Puts the given status effect on the player for the given number of turns
+    Status { kind: StatusKind, turns: i32 }
+}
+
+impl Item
+{
+    /// Returns what the item has to be aimed at when used, None if it isn't aimed
+    pub fn targeting(self) -> Option< TargetSpec >
+    {
+        match self
+        {
+            Item::Heal { .. } | Item::Equip | Item::Lightning { .. } | Item::Status { .. } => None,
+            Item::Fireball { radius, range, .. } => Some(TargetSpec { range: Some(range), radius: radius, monster_only: false }),
+            Item::Confusion { range, .. } => Some(TargetSpec { range: Some(range), radius: 0, monster_only: true })
+        }
+    }
 }
 
 enum ItemUseResult
@@ -23,58 +58,72 @@ enum ItemUseResult
     Cancelled
 }
 
-pub fn use_item(inv_id: usize, game: &mut Game, tcod: &mut TCOD)
+/// Uses the item at the given inventory index. Items that have to be aimed
+/// are cancelled unless they're given a valid target. Returns false if the
+/// item wasn't used.
+pub fn use_item(inv_id: usize, target: Option< (i32, i32) >, game: &mut Game) -> bool
 {
-    if let Some(item) = game.inventory[inv_id].item
+    if let Some(item) = game.objects[PLAYER_ID].inventory[inv_id].item
     {
-        let on_use = match item
+        let aimed = item.targeting().map_or(true, |spec| target.map_or(false, |t| spec.is_valid(game, t)));
+        let result = match item
         {
-            Item::HealthPotion => use_health_potion,
-            Item::Sword => toggle_equipment,
-            Item::Shield => toggle_equipment,
-            Item::PlateArmor => toggle_equipment
+            _ if !aimed => ItemUseResult::Cancelled,
+            Item::Heal { amount } => use_heal(amount, game),
+            Item::Equip => toggle_equipment(inv_id, game),
+            Item::Lightning { damage, range } => use_lightning(damage, range, game),
+            Item::Fireball { damage, radius, .. } => use_fireball(damage, radius, target.unwrap(), game),
+            Item::Confusion { turns, .. } => use_confusion(turns, target.unwrap(), game),
+            Item::Status { kind, turns } => use_status(kind, turns, game)
         };
 
-        match on_use(inv_id, game, tcod)
+        match result
         {
-            ItemUseResult::Used         => { game.inventory.remove(inv_id); },
-            ItemUseResult::UsedAndKept  => {  },
-            ItemUseResult::Cancelled    => { game.log.add("Cancelled.", colors::WHITE); }
+            ItemUseResult::Used         => { game.objects[PLAYER_ID].inventory.remove(inv_id); true },
+            ItemUseResult::UsedAndKept  => true,
+            ItemUseResult::Cancelled    => { game.log.add("Cancelled.", colors::WHITE); false }
         }
     }
     else
     {
-        game.log.add(format!("The {} cannot be used.", game.inventory[inv_id].name), colors::WHITE);
+        game.log.add(format!("The {} cannot be used.", game.objects[PLAYER_ID].inventory[inv_id].name), colors::WHITE);
+        false
     }
 }
 
-pub fn pick_item_up(id: usize, game: &mut Game)
+/// Picks up the given item. Returns false if the player's inventory is full.
+pub fn pick_item_up(id: ObjectId, game: &mut Game) -> bool
 {
-    if game.inventory.len() >= 26
+    if game.objects[PLAYER_ID].inventory.len() >= MAX_INVENTORY
     {
         game.log.add(format!("Your inventory is full! Cannot pick up {}!", game.objects[id].name), colors::RED);
+        false
     }
     else
     {
-        let item = game.objects.swap_remove(id);
+        let item = game.objects.remove(id).unwrap();
         game.log.add(format!("You picked up {}!", item.name), colors::GREEN);
-        let index = game.inventory.len();
+        let index = game.objects[PLAYER_ID].inventory.len();
         let slot = item.equipment.map(|e| e.slot);
-        game.inventory.push(item);
+        game.objects[PLAYER_ID].inventory.push(item);
 
         if let Some(slot) = slot
         {
-            if get_equipped_in_slot(slot, &game.inventory).is_none()
+            if get_equipped_in_slot(slot, &game.objects[PLAYER_ID].inventory).is_none()
             {
-                game.inventory[index].equip(&mut game.log);
+                game.objects[PLAYER_ID].inventory[index].equip(&mut game.log);
             }
         }
+
+        true
     }
 }
 
-pub fn drop_item(inv_id: usize, game: &mut Game)
+/// Drops the item at the given inventory index under the player. Returns
+/// true, since there's nothing that can stop the player from dropping an item.
+pub fn drop_item(inv_id: usize, game: &mut Game) -> bool
 {
-    let mut item = game.inventory.remove(inv_id);
+    let mut item = game.objects[PLAYER_ID].inventory.remove(inv_id);
     if item.equipment.is_some()
     {
         item.unequip(&mut game.log);
@@ -82,12 +131,14 @@ pub fn drop_item(inv_id: usize, game: &mut Game)
 
     item.set_pos(game.objects[PLAYER_ID].pos.0, game.objects[PLAYER_ID].pos.1);
     game.log.add(format!("You dropped {}!", item.name), colors::YELLOW);
-    game.objects.push(item);
+    game.objects.insert(item);
+
+    true
 }
 
-fn use_health_potion(_inv_id: usize, game: &mut Game, _tcod: &mut TCOD) -> ItemUseResult
+fn use_heal(amount: i32, game: &mut Game) -> ItemUseResult
 {
-    if let Some(fighter) = game.objects[PLAYER_ID].fighter
+    if let Some(fighter) = game.objects[PLAYER_ID].fighter.as_ref()
     {
         if fighter.hp == fighter.max_hp
         {
@@ -96,52 +147,179 @@ fn use_health_potion(_inv_id: usize, game: &mut Game, _tcod: &mut TCOD) -> ItemU
         }
 
         game.log.add("Your wounds start to feel better.", colors::LIGHT_VIOLET);
-        game.objects[PLAYER_ID].heal(HEALTH_POTION_HEAL_AMT);
+        game.objects[PLAYER_ID].heal(amount);
+        return ItemUseResult::Used;
+    }
+
+    ItemUseResult::Cancelled
+}
+
+fn use_lightning(damage: i32, range: i32, game: &mut Game) -> ItemUseResult
+{
+    let spec = TargetSpec { range: Some(range), radius: 0, monster_only: true };
+    let target = match targeting::nearest_monster(game, &spec)
+    {
+        Some(target) => target,
+        None =>
+        {
+            game.log.add("No enemy is close enough to strike.", colors::RED);
+            return ItemUseResult::Cancelled;
+        }
+    };
+
+    let damage = damage + game.objects[PLAYER_ID].intelligence_value();
+    game.log.add(format!("A lightning bolt strikes the {} with a loud thunder for {} damage!", game.objects[target].name, damage), colors::LIGHT_BLUE);
+    let pos = game.objects[target].pos;
+    magic_damage(target, damage, game);
+    ai::make_noise(pos, ai::MAGIC_NOISE, game);
+
+    ItemUseResult::Used
+}
+
+fn use_fireball(damage: i32, radius: i32, target: (i32, i32), game: &mut Game) -> ItemUseResult
+{
+    let damage = damage + game.objects[PLAYER_ID].intelligence_value();
+    game.log.add(format!("The fireball explodes, burning everything within {} tiles!", radius), colors::ORANGE);
+
+    let area = targeting::area(game, target, radius);
+    let burned: Vec< ObjectId > = game.objects
+        .iter()
+        .filter(|(_, o)| o.alive && o.fighter.is_some() && area.contains(&o.pos))
+        .map(|(id, _)| id)
+        .collect();
+
+    for id in burned
+    {
+        game.log.add(format!("The {} gets burned for {} damage.", game.objects[id].name, damage), colors::ORANGE);
+        magic_damage(id, damage, game);
+    }
+    ai::make_noise(target, ai::MAGIC_NOISE, game);
+
+    ItemUseResult::Used
+}
+
+fn use_confusion(turns: i32, target: (i32, i32), game: &mut Game) -> ItemUseResult
+{
+    let target = match targeting::monster_at(game, target)
+    {
+        Some(target) => target,
+        None => return ItemUseResult::Cancelled
+    };
+
+    let turns = turns + game.objects[PLAYER_ID].intelligence_value() / 2;
+    if let Some(fighter) = game.objects[target].fighter.as_mut()
+    {
+        fighter.add_effect(StatusKind::Confusion, turns);
+    }
+    game.log.add(format!("The eyes of the {} look vacant, as it starts to stumble around!", game.objects[target].name), colors::LIGHT_GREEN);
+
+    ItemUseResult::Used
+}
+
+fn use_status(kind: StatusKind, turns: i32, game: &mut Game) -> ItemUseResult
+{
+    if let Some(fighter) = game.objects[PLAYER_ID].fighter.as_mut()
+    {
+        fighter.add_effect(kind, turns);
+        game.log.add(format!("You are {}!", kind.name().to_lowercase()), colors::LIGHT_VIOLET);
         return ItemUseResult::Used;
     }
 
     ItemUseResult::Cancelled
 }
 
-fn toggle_equipment(inv_id: usize, game: &mut Game, _tcod: &mut TCOD) -> ItemUseResult
+/// Deals damage from one of the player's spells to the given fighter. The
+/// player gets the experience for anything it kills.
+fn magic_damage(target: ObjectId, damage: i32, game: &mut Game)
 {
-    let equipment = match game.inventory[inv_id].equipment
+    let name = game.objects[target].name.clone();
+    if let Some(xp) = game.objects[target].take_damage(damage, &mut game.log)
+    {
+        if target != PLAYER_ID
+        {
+            if let Some(fighter) = game.objects[PLAYER_ID].fighter.as_mut()
+            {
+                fighter.xp += xp;
+            }
+        }
+        game.record_death(PLAYER_ID, target, name);
+    }
+}
+
+fn toggle_equipment(inv_id: usize, game: &mut Game) -> ItemUseResult
+{
+    let equipment = match game.objects[PLAYER_ID].inventory[inv_id].equipment
     {
         Some(equipment) => equipment,
         None => return ItemUseResult::Cancelled
     };
 
-    if let Some(old) = get_equipped_in_slot(equipment.slot, &game.inventory)
+    if let Some(old) = get_equipped_in_slot(equipment.slot, &game.objects[PLAYER_ID].inventory)
     {
-        game.inventory[old].unequip(&mut game.log);
+        game.objects[PLAYER_ID].inventory[old].unequip(&mut game.log);
     }
 
     if equipment.equipped
     {
-        game.inventory[inv_id].unequip(&mut game.log);
+        game.objects[PLAYER_ID].inventory[inv_id].unequip(&mut game.log);
     }
     else
     {
-        game.inventory[inv_id].equip(&mut game.log);
+        game.objects[PLAYER_ID].inventory[inv_id].equip(&mut game.log);
     }
 
     ItemUseResult::UsedAndKept
 }
 
+/// Represents a piece of equipment. Everything but the slot can be left out
+/// of the content files and defaults to false/0.
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Equipment
 {
     pub slot: EquipmentSlot,
+    #[serde(default)]
     pub equipped: bool,
+    #[serde(default)]
     pub vit_bonus: i32,
+    #[serde(default)]
     pub atk_bonus: i32,
+    #[serde(default)]
     pub str_bonus: i32,
+    #[serde(default)]
     pub def_bonus: i32,
+    #[serde(default)]
     pub dex_bonus: i32,
+    #[serde(default)]
     pub int_bonus: i32,
+    #[serde(default)]
     pub lck_bonus: i32
 }
 
+impl Equipment
+{
+    /// Returns the non-zero stat bonuses of this equipment, e.g. "+2 ATK, +1 STR"
+    pub fn bonus_summary(&self) -> String
+    {
+        let bonuses = [
+            (self.vit_bonus, "VIT"),
+            (self.atk_bonus, "ATK"),
+            (self.str_bonus, "STR"),
+            (self.def_bonus, "DEF"),
+            (self.dex_bonus, "DEX"),
+            (self.int_bonus, "INT"),
+            (self.lck_bonus, "LCK")
+        ];
+
+        let summary: Vec< String > = bonuses
+            .iter()
+            .filter(|(bonus, _)| *bonus != 0)
+            .map(|(bonus, stat)| format!("{:+} {}", bonus, stat))
+            .collect();
+
+        if summary.is_empty() { "no bonuses".to_string() } else { summary.join(", ") }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum EquipmentSlot
 {
@@ -0,0 +1,289 @@
+use crate::paths;
+
+use tcod::input::{ Key, KeyCode };
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+const KEYMAP_FILE: &str = "keymap.json";
+
+/// Names of the non-printable keys that can be used in the keymap file.
+/// Printable keys are written as the character itself, e.g. "r" or "?", with
+/// upper case letters meaning the letter is typed while holding shift.
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Insert", KeyCode::Insert),
+    ("Delete", KeyCode::Delete),
+    ("Escape", KeyCode::Escape),
+    ("Enter", KeyCode::Enter),
+    ("Space", KeyCode::Spacebar),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("NumPad0", KeyCode::NumPad0),
+    ("NumPad1", KeyCode::NumPad1),
+    ("NumPad2", KeyCode::NumPad2),
+    ("NumPad3", KeyCode::NumPad3),
+    ("NumPad4", KeyCode::NumPad4),
+    ("NumPad5", KeyCode::NumPad5),
+    ("NumPad6", KeyCode::NumPad6),
+    ("NumPad7", KeyCode::NumPad7),
+    ("NumPad8", KeyCode::NumPad8),
+    ("NumPad9", KeyCode::NumPad9),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12)
+];
+
+/// Everything the player can do with a key while playing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Command
+{
+    MoveNorth,
+    MoveSouth,
+    MoveWest,
+    MoveEast,
+    MoveNorthWest,
+    MoveNorthEast,
+    MoveSouthWest,
+    MoveSouthEast,
+    Wait,
+    Rest,
+    Explore,
+    ToggleAutoPickup,
+    Interact,
+    Inventory,
+    Drop,
+    Look,
+    Character,
+    Help,
+    Exit
+}
+
+impl Command
+{
+    pub const ALL: [Command; 19] = [
+        Command::MoveNorth,
+        Command::MoveSouth,
+        Command::MoveWest,
+        Command::MoveEast,
+        Command::MoveNorthWest,
+        Command::MoveNorthEast,
+        Command::MoveSouthWest,
+        Command::MoveSouthEast,
+        Command::Wait,
+        Command::Rest,
+        Command::Explore,
+        Command::ToggleAutoPickup,
+        Command::Interact,
+        Command::Inventory,
+        Command::Drop,
+        Command::Look,
+        Command::Character,
+        Command::Help,
+        Command::Exit
+    ];
+
+    /// Returns the direction a movement command moves the player in
+    pub fn direction(self) -> Option< (i32, i32) >
+    {
+        match self
+        {
+            Command::MoveNorth => Some((0, -1)),
+            Command::MoveSouth => Some((0, 1)),
+            Command::MoveWest => Some((-1, 0)),
+            Command::MoveEast => Some((1, 0)),
+            Command::MoveNorthWest => Some((-1, -1)),
+            Command::MoveNorthEast => Some((1, -1)),
+            Command::MoveSouthWest => Some((-1, 1)),
+            Command::MoveSouthEast => Some((1, 1)),
+            _ => None
+        }
+    }
+
+    /// Returns what the command does, as shown on the help screen
+    pub fn description(self) -> &'static str
+    {
+        match self
+        {
+            Command::MoveNorth => "Move or attack north",
+            Command::MoveSouth => "Move or attack south",
+            Command::MoveWest => "Move or attack west",
+            Command::MoveEast => "Move or attack east",
+            Command::MoveNorthWest => "Move or attack north-west",
+            Command::MoveNorthEast => "Move or attack north-east",
+            Command::MoveSouthWest => "Move or attack south-west",
+            Command::MoveSouthEast => "Move or attack south-east",
+            Command::Wait => "Wait a turn",
+            Command::Rest => "Rest until healed",
+            Command::Explore => "Explore the level",
+            Command::ToggleAutoPickup => "Turn auto-pickup on or off",
+            Command::Interact => "Pick up an item or take the stairs",
+            Command::Inventory => "Use an item",
+            Command::Drop => "Drop an item",
+            Command::Look => "Look around",
+            Command::Character => "Character information",
+            Command::Help => "Show this help",
+            Command::Exit => "Save and quit"
+        }
+    }
+
+    /// Returns the keys bound to the command when there is no keymap file
+    fn default_keys(self) -> &'static [&'static str]
+    {
+        match self
+        {
+            Command::MoveNorth => &["Up", "w", "k", "NumPad8"],
+            Command::MoveSouth => &["Down", "s", "j", "NumPad2"],
+            Command::MoveWest => &["Left", "a", "h", "NumPad4"],
+            Command::MoveEast => &["Right", "d", "l", "NumPad6"],
+            Command::MoveNorthWest => &["Home", "y", "NumPad7"],
+            Command::MoveNorthEast => &["PageUp", "u", "NumPad9"],
+            Command::MoveSouthWest => &["End", "b", "NumPad1"],
+            Command::MoveSouthEast => &["PageDown", "n", "NumPad3"],
+            Command::Wait => &["r", "NumPad5"],
+            Command::Rest => &["R"],
+            Command::Explore => &["e"],
+            Command::ToggleAutoPickup => &["p"],
+            Command::Interact => &["f"],
+            Command::Inventory => &["i"],
+            Command::Drop => &["o"],
+            Command::Look => &["x"],
+            Command::Character => &["c"],
+            Command::Help => &["?", "F1"],
+            Command::Exit => &["Escape"]
+        }
+    }
+}
+
+/// Maps key names to the commands they trigger
+#[derive(Debug, Clone)]
+pub struct Keymap
+{
+    bindings: BTreeMap< String, Command >
+}
+
+impl Default for Keymap
+{
+    fn default() -> Self
+    {
+        let mut keymap = Keymap { bindings: BTreeMap::new() };
+        keymap.add_missing_defaults();
+        keymap
+    }
+}
+
+impl Keymap
+{
+    /// Returns the command bound to the given key press, if there is one
+    pub fn command(&self, key: Key) -> Option< Command >
+    {
+        key_name(key).and_then(|name| self.bindings.get(&name).cloned())
+    }
+
+    /// Returns the names of all of the keys bound to the given command
+    pub fn keys(&self, command: Command) -> Vec< &str >
+    {
+        self.bindings
+            .iter()
+            .filter(|(_, c)| **c == command)
+            .map(|(key, _)| key.as_str())
+            .collect()
+    }
+
+    /// Binds the default keys of every command that has no keys bound to it.
+    /// Default keys that are already bound to something else are left alone.
+    fn add_missing_defaults(&mut self)
+    {
+        for command in Command::ALL.iter().cloned()
+        {
+            if !self.bindings.values().any(|c| *c == command)
+            {
+                for key in command.default_keys()
+                {
+                    self.bindings.entry(key.to_string()).or_insert(command);
+                }
+            }
+        }
+    }
+}
+
+/// Loads the keymap from the user's config directory. When there is no keymap
+/// file yet the default one is written there so it can be edited. Commands
+/// that aren't in the file (e.g. ones added since it was written) keep their
+/// default keys.
+pub fn load_keymap() -> Result< Keymap, Box< dyn Error > >
+{
+    let path = keymap_path();
+    if !path.exists()
+    {
+        let keymap = Keymap::default();
+
+        // Failing to write the file only means the defaults can't be edited
+        let _ = fs::create_dir_all(paths::config_dir())
+            .and_then(|_| fs::write(&path, serde_json::to_string_pretty(&keymap.bindings).unwrap()));
+        return Ok(keymap);
+    }
+
+    let json = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let bindings: BTreeMap< String, Command > = serde_json::from_str(&json).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if let Some(key) = bindings.keys().find(|k| !is_valid_key_name(k))
+    {
+        return Err(format!("{}: unknown key \"{}\"", path.display(), key).into());
+    }
+
+    let mut keymap = Keymap { bindings: bindings };
+    keymap.add_missing_defaults();
+    Ok(keymap)
+}
+
+/// Returns the path of the keymap file
+fn keymap_path() -> PathBuf
+{
+    paths::config_dir().join(KEYMAP_FILE)
+}
+
+/// Returns true if the given name is a single printable character or one of
+/// the names in `KEY_NAMES`
+fn is_valid_key_name(name: &str) -> bool
+{
+    let mut chars = name.chars();
+    match (chars.next(), chars.next())
+    {
+        (Some(c), None) => !c.is_control() && !c.is_whitespace(),
+        _ => KEY_NAMES.iter().any(|(n, _)| *n == name)
+    }
+}
+
+/// Returns the keymap name of the given key press
+fn key_name(key: Key) -> Option< String >
+{
+    if let Some((name, _)) = KEY_NAMES.iter().find(|(_, code)| *code == key.code)
+    {
+        return Some(name.to_string());
+    }
+
+    match key.printable
+    {
+        c if c.is_alphabetic() && key.shift => Some(c.to_uppercase().collect()),
+        c if c != '\0' && !c.is_control() && !c.is_whitespace() => Some(c.to_string()),
+        _ => None
+    }
+}
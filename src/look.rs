@@ -0,0 +1,170 @@
+use crate::{ TCOD, SCREEN_WIDTH, SCREEN_HEIGHT };
+use crate::game::{ Game, PLAYER_ID };
+use crate::object::Object;
+use crate::entity::ObjectId;
+use crate::fighter::Fighter;
+use crate::item::Item;
+use crate::keymap::Command;
+
+use tcod::colors;
+use tcod::console::*;
+
+const LOOK_PANEL_WIDTH: i32 = 36;
+
+/// Lets the player move a cursor over the map with the movement keys and shows
+/// a description of whatever is under it. The cursor starts on the player and
+/// look mode ends on the look key or escape.
+pub fn look_mode(game: &mut Game, tcod: &mut TCOD)
+{
+    let mut cursor = game.objects[PLAYER_ID].pos;
+    while !tcod.root.window_closed()
+    {
+        game.render(tcod);
+        tcod.root.set_char_background(cursor.0, cursor.1, colors::LIGHT_GREY, BackgroundFlag::Set);
+        render_description(&describe(game, cursor), cursor, tcod);
+        tcod.root.flush();
+
+        let key = tcod.root.wait_for_keypress(true);
+        match tcod.keymap.command(key)
+        {
+            Some(Command::Look) | Some(Command::Exit) => break,
+            Some(command) =>
+            {
+                if let Some((dx, dy)) = command.direction()
+                {
+                    let next = (cursor.0 + dx, cursor.1 + dy);
+                    if game.map.in_bounds(next)
+                    {
+                        cursor = next;
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+/// Describes the tile at the given position and everything the player can
+/// see (or remembers) on it, one line each
+pub fn describe(game: &Game, pos: (i32, i32)) -> Vec< String >
+{
+    if !game.map.in_bounds(pos) || !game.map.is_explored(pos)
+    {
+        return vec!["You haven't explored this place yet.".to_string()];
+    }
+
+    let visible = game.map.is_in_fov(pos);
+    let terrain = if game.map.tiles[pos.0 as usize][pos.1 as usize].blocked { "A stone wall" } else { "Stone floor" };
+    let mut lines = vec![if visible { format!("{}.", terrain) } else { format!("{} (remembered).", terrain) }];
+
+    // Living things first, then items and everything else
+    let mut objects: Vec< (ObjectId, &Object) > = game.objects
+        .iter()
+        .filter(|(_, o)| o.pos == pos && (visible || o.always_visible))
+        .collect();
+    objects.sort_by_key(|(_, o)| !o.alive);
+
+    for (id, object) in objects
+    {
+        let remembered = if visible { "" } else { " (remembered)" };
+        lines.push(format!("\n{}{}", object.name, remembered));
+        if let (Some(fighter), true) = (object.fighter.as_ref(), object.alive)
+        {
+            lines.push(describe_fighter(object, fighter, id == PLAYER_ID));
+        }
+        if let Some(state) = object.ai.as_ref().and_then(|ai| ai.description())
+        {
+            lines.push(format!("{}.", state));
+        }
+        if let Some(item) = object.item
+        {
+            lines.push(describe_item(object, item));
+        }
+    }
+
+    lines
+}
+
+/// Describes how hurt a fighter is along with its stats and what it has equipped
+fn describe_fighter(object: &Object, fighter: &Fighter, is_player: bool) -> String
+{
+    let mut description = format!("{}, level {}.\nATK {}  STR {}  DEF {}  DEX {}  SPD {}",
+        health_description(fighter),
+        object.level,
+        object.attack_value(),
+        object.strength_value(),
+        object.defense_value(),
+        object.dexterity_value(),
+        object.speed_value(is_player));
+
+    let equipped: Vec< &str > = object.inventory
+        .iter()
+        .filter(|i| i.equipment.map_or(false, |e| e.equipped))
+        .map(|i| i.name.as_str())
+        .collect();
+    if !equipped.is_empty()
+    {
+        description.push_str(&format!("\nEquipped: {}", equipped.join(", ")));
+    }
+
+    if !fighter.effects.is_empty()
+    {
+        let effects: Vec< &str > = fighter.effects.iter().map(|e| e.kind.name()).collect();
+        description.push_str(&format!("\n{}.", effects.join(", ")));
+    }
+
+    description
+}
+
+/// Describes what an item does when used or equipped
+fn describe_item(object: &Object, item: Item) -> String
+{
+    match (item, object.equipment)
+    {
+        (Item::Heal { amount }, _) => format!("Heals {} HP when used.", amount),
+        (Item::Equip, Some(equipment)) => format!("Worn on the {}: {}.", equipment.slot, equipment.bonus_summary()),
+        (Item::Equip, None) => "Can be equipped.".to_string(),
+        (Item::Lightning { damage, range }, _) => format!("Strikes the closest monster within {} tiles for {} damage, more with intelligence.", range, damage),
+        (Item::Fireball { damage, radius, range }, _) => format!("Explodes up to {} tiles away, burning everything within {} tiles for {} damage, more with intelligence.", range, radius, damage),
+        (Item::Confusion { turns, range }, _) => format!("Confuses a monster up to {} tiles away for {} turns, longer with intelligence.", range, turns),
+        (Item::Status { kind, turns }, _) => format!("Makes you {} for {} turns.", kind.name().to_lowercase(), turns)
+    }
+}
+
+/// Returns roughly how hurt a fighter is
+fn health_description(fighter: &Fighter) -> &'static str
+{
+    let health = fighter.hp as f32 / fighter.max_hp as f32;
+    if health >= 1.0
+    {
+        "Unhurt"
+    }
+    else if health > 0.66
+    {
+        "Lightly wounded"
+    }
+    else if health > 0.33
+    {
+        "Wounded"
+    }
+    else
+    {
+        "Badly wounded"
+    }
+}
+
+/// Renders the description panel on the side of the screen away from the cursor
+fn render_description(lines: &[String], cursor: (i32, i32), tcod: &mut TCOD)
+{
+    let text = lines.join("\n");
+    let height = tcod.root.get_height_rect(0, 0, LOOK_PANEL_WIDTH, SCREEN_HEIGHT, &text);
+
+    let mut panel = Offscreen::new(LOOK_PANEL_WIDTH, height);
+    panel.set_default_background(colors::BLACK);
+    panel.set_default_foreground(colors::WHITE);
+    panel.clear();
+    panel.print_rect_ex(0, 0, LOOK_PANEL_WIDTH, height, BackgroundFlag::None, TextAlignment::Left, text);
+
+    let x = if cursor.0 < SCREEN_WIDTH / 2 { SCREEN_WIDTH - LOOK_PANEL_WIDTH - 1 } else { 1 };
+    blit(&panel, (0, 0), (LOOK_PANEL_WIDTH, height), &mut tcod.root, (x, 1), 1.0, 0.8);
+}
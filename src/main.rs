@@ -1,5 +1,6 @@
 extern crate tcod;
 extern crate rand;
+extern crate rand_pcg;
 extern crate serde;
 extern crate serde_json;
 #[macro_use]
@@ -8,14 +9,26 @@ extern crate serde_derive;
 mod game;
 mod map;
 mod object;
+mod entity;
 mod fighter;
 mod ai;
 mod item;
 mod menu;
 mod gui;
+mod content;
+mod replay;
+mod save;
+mod paths;
+mod morgue;
+mod keymap;
+mod path;
+mod activity;
+mod look;
+mod targeting;
 
 use crate::map::{ MAP_WIDTH, MAP_HEIGHT };
 use crate::gui::PANEL_HEIGHT;
+use crate::keymap::Keymap;
 
 use tcod::console::*;
 use tcod::input::Mouse;
@@ -29,7 +42,8 @@ pub struct TCOD
     pub root: Root,
     pub con: Offscreen,
     pub gui: Offscreen,
-    pub mouse: Mouse
+    pub mouse: Mouse,
+    pub keymap: Keymap
 }
 
 fn main() 
@@ -47,7 +61,8 @@ fn main()
         root: root,
         con: Offscreen::new(MAP_WIDTH, MAP_HEIGHT),
         gui: Offscreen::new(SCREEN_WIDTH, PANEL_HEIGHT),
-        mouse: Default::default()
+        mouse: Default::default(),
+        keymap: Keymap::default()
     };
 
     // Show main menu
@@ -1,19 +1,25 @@
 use crate::object::Object;
-use crate::game::PLAYER_ID;
-use crate::fighter::{ Fighter, DeathCallback };
+use crate::entity::Objects;
+use crate::game::{ PLAYER_ID, GameRng };
 use crate::ai::Ai;
-use crate::item::*;
+use crate::content::Content;
 
 use tcod::colors;
 use tcod::chars;
 use tcod::console::{ Console, BackgroundFlag };
 use tcod::map::{ Map as FovMap, FovAlgorithm };
-use rand::{ Rng, distributions::WeightedIndex, prelude::* };
+use rand::{ Rng, distributions::{ Distribution, WeightedIndex } };
 use std::cmp;
 
 pub const MAP_WIDTH: i32 = 80;
 pub const MAP_HEIGHT: i32 = 40;
 
+/// How far the player can see
+pub const FOV_RADIUS: i32 = 10;
+
+/// How far the player can see while blind
+pub const BLIND_FOV_RADIUS: i32 = 1;
+
 const ROOM_MIN_SIZE: i32 = 6;
 const ROOM_MAX_SIZE: i32 = 10;
 const MAX_ROOM_COUNT: i32 = 30;
@@ -31,6 +37,10 @@ pub struct Map
     /// The height of the map
     pub height: i32,
 
+    /// The center of every room, in the order they were dug out
+    #[serde(default)]
+    pub rooms: Vec< (i32, i32) >,
+
     /// The map's FOV map
     #[serde(skip)]
     fov_wrapper: FovWrapper
@@ -46,6 +56,7 @@ impl Map
             tiles: vec![vec![Tile::empty(); MAP_HEIGHT as usize]; MAP_WIDTH as usize],
             width: MAP_WIDTH,
             height: MAP_HEIGHT,
+            rooms: vec![],
             fov_wrapper: FovWrapper::new()
         };
         map.generate_fov_map();
@@ -53,24 +64,25 @@ impl Map
         map
     }
 
-    /// Function to generate the map
-    pub fn generate(&mut self, objects: &mut Vec< Object >, dungeon_level: i32)
+    /// Function to generate the map. All randomness comes from the given rng
+    /// so the same rng state always generates the same map
+    pub fn generate(&mut self, objects: &mut Objects, dungeon_level: i32, content: &Content, rng: &mut GameRng)
     {
         self.tiles = vec![vec![Tile::wall(); self.height as usize]; self.width as usize];
         let mut rooms: Vec< Rect > = vec![];
         
-        // Remove everything except player from objects vec when generating a new map
-        objects.truncate(1);
+        // Remove everything except player from the objects when generating a new map
+        objects.retain(|id, _| id == PLAYER_ID);
 
         for _ in 0..MAX_ROOM_COUNT
         {
             // Generate random size for room
-            let room_w = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-            let room_h = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+            let room_w = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
+            let room_h = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
 
             // Generate random position for room
-            let room_x = rand::thread_rng().gen_range(0, self.width - room_w);
-            let room_y = rand::thread_rng().gen_range(0, self.height - room_h);
+            let room_x = rng.gen_range(0, self.width - room_w);
+            let room_y = rng.gen_range(0, self.height - room_h);
 
             // Create a new rect from the generated position and size
             let new_room = Rect::new(room_x, room_y, room_w, room_h);
@@ -91,7 +103,7 @@ impl Map
                 {
                     // Connect all the rooms after first with tunnels & put stuff in em
                     let (prev_x, prev_y) = rooms[rooms.len() - 1].get_center();
-                    if rand::random()
+                    if rng.gen()
                     {
                         self.generate_horizontal_tunnel(prev_x, new_x, prev_y);
                         self.generate_vertical_tunnel(prev_y, new_y, new_x);
@@ -102,24 +114,26 @@ impl Map
                         self.generate_horizontal_tunnel(prev_x, new_x, new_y);
                     }
 
-                    self.populate_room(&new_room, objects, dungeon_level);
+                    self.populate_room(&new_room, rooms.len(), objects, dungeon_level, content, rng);
                 }
 
                 rooms.push(new_room);
             }
         }
 
+        self.rooms = rooms.iter().map(|r| r.get_center()).collect();
+
         // Generate stairs at center of last room
         let (stair_x, stair_y) = rooms[rooms.len() - 1].get_center();
         let mut stairs = Object::new(stair_x, stair_y, 'H', colors::WHITE, "Stairs", false);
         stairs.always_visible = true;
-        objects.push(stairs);
+        objects.insert(stairs);
 
         self.generate_fov_map();
     }
 
     /// Draws the map to the given TCOD console
-    pub fn draw(&mut self, con: &mut Console)
+    pub fn draw(&self, con: &mut Console)
     {
         for y in 0..self.height
         {
@@ -135,13 +149,7 @@ impl Map
                     (true, true)    => colors::DARK_GREY
                 };
 
-                let explored = &mut self.tiles[x as usize][y as usize].explored;
-                if visible
-                {
-                    *explored = true;
-                }
-
-                if *explored
+                if self.tiles[x as usize][y as usize].explored
                 {
                     con.set_char_background(x, y, color, BackgroundFlag::Set);
                 }
@@ -149,9 +157,14 @@ impl Map
         }
     }
 
-    /// Creates the FOV map
+    /// Creates the FOV map from the map's tiles
     pub fn generate_fov_map(&mut self)
     {
+        if self.fov_wrapper.fov.size() != (self.width, self.height)
+        {
+            self.fov_wrapper = FovWrapper { fov: FovMap::new(self.width, self.height) };
+        }
+
         for y in 0..self.height
         {
             for x in 0..self.width
@@ -161,10 +174,21 @@ impl Map
         }
     }
 
-    /// Recomputes the player's FOV
-    pub fn recompute_fov(&mut self, pos: (i32, i32))
+    /// Recomputes the player's FOV and marks every tile in it as explored
+    pub fn recompute_fov(&mut self, pos: (i32, i32), radius: i32)
     {
-        self.fov_wrapper.fov.compute_fov(pos.0, pos.1, 10, true, FovAlgorithm::Basic);
+        self.fov_wrapper.fov.compute_fov(pos.0, pos.1, radius, true, FovAlgorithm::Basic);
+
+        for y in 0..self.height
+        {
+            for x in 0..self.width
+            {
+                if self.is_in_fov((x, y))
+                {
+                    self.tiles[x as usize][y as usize].explored = true;
+                }
+            }
+        }
     }
 
     /// Returns true if the given position is in the player's FOV
@@ -174,13 +198,29 @@ impl Map
     }
 
     /// Returns true if the tile at the given position is blocked (either a wall or occupied)
-    pub fn is_blocked(&self, x: i32, y: i32, objects: &[Object]) -> bool
+    pub fn is_blocked(&self, x: i32, y: i32, objects: &Objects) -> bool
     {
         if self.tiles[x as usize][y as usize].blocked
         {
             return true;
         }
-        objects.iter().any(|o| { o.solid && o.pos.0 == x && o.pos.1 == y })
+        objects.values().any(|o| { o.solid && o.pos.0 == x && o.pos.1 == y })
+    }
+
+    /// Returns true if stepping diagonally from the given position by (dx, dy)
+    /// would cut around the corner of a wall. Nothing can move or attack
+    /// diagonally past a wall, the same as monsters can't see through one.
+    /// The edge of the map counts as a wall.
+    pub fn cuts_corner(&self, pos: (i32, i32), dx: i32, dy: i32) -> bool
+    {
+        let is_wall = |x: i32, y: i32| !self.in_bounds((x, y)) || self.tiles[x as usize][y as usize].blocked;
+        dx != 0 && dy != 0 && (is_wall(pos.0 + dx, pos.1) || is_wall(pos.0, pos.1 + dy))
+    }
+
+    /// Returns true if the given position is inside the map
+    pub fn in_bounds(&self, pos: (i32, i32)) -> bool
+    {
+        pos.0 >= 0 && pos.1 >= 0 && pos.0 < self.width && pos.1 < self.height
     }
 
     /// Returns true if the tile at the given position has been explored
@@ -221,8 +261,27 @@ impl Map
     }
 
     /// Function to spawn monsters and items in the given room
-    fn populate_room(&mut self, room: &Rect, objects: &mut Vec< Object >, dungeon_level: i32)
+    fn populate_room(&mut self, room: &Rect, room_id: usize, objects: &mut Objects, dungeon_level: i32, content: &Content, rng: &mut GameRng)
     {
+        // The weights for each different type of item to spawn come from the
+        // item templates
+        let item_weights: Vec< i32 > = content.items
+            .iter()
+            .map(|i| from_dungeon_level(&i.weights, dungeon_level))
+            .collect();
+
+        // Distribution using weighted index sampling to determine the type of
+        // item that is spawned. There might not be any items for this level
+        let item_dist = WeightedIndex::new(&item_weights).ok();
+
+        // Same as above but only for equipment, used to hand out gear to monsters
+        let gear_weights: Vec< i32 > = content.items
+            .iter()
+            .zip(item_weights.iter())
+            .map(|(item, &weight)| if item.equipment.is_some() { weight } else { 0 })
+            .collect();
+        let gear_dist = WeightedIndex::new(&gear_weights).ok();
+
         // Maximum number of monsters that can spawn in a room is determined by dungeon level
         let max_monsters = from_dungeon_level(&[
             Transition { level: 1, value: 2 },  // Levels 1-3: 2 monsters max per room 
@@ -231,65 +290,66 @@ impl Map
             dungeon_level
         );
 
+        // Chance (out of 100) for a monster to spawn wielding a piece of equipment
+        let monster_gear_chance = from_dungeon_level(&[
+            Transition { level: 1, value: 10 },
+            Transition { level: 4, value: 20 },
+            Transition { level: 8, value: 30 }],
+            dungeon_level
+        );
+
         // Number of monsters in the room is a random number [0, max]
-        let num_monsters = rand::thread_rng().gen_range(0, max_monsters + 1);
-
-        // The different types of monsters that can spawn
-        let monster_choices = [
-            "Orc", 
-            "Troll"
-        ];
-
-        // The weighted probabilities for each type of monster to spawn
-        let monster_weights = [
-            // Orc monster weight
-            80, 
-
-            // Troll Monster weight
-            from_dungeon_level(&[
-                Transition { level: 3, value: 15 }, 
-                Transition { level: 5, value: 30 }, 
-                Transition { level: 8, value: 50 }], 
-                dungeon_level
-            )
-        ];
+        let num_monsters = rng.gen_range(0, max_monsters + 1);
+
+        // The weighted probabilities for each type of monster to spawn come 
+        // from the monster templates
+        let monster_weights: Vec< i32 > = content.monsters
+            .iter()
+            .map(|m| from_dungeon_level(&m.weights, dungeon_level))
+            .collect();
 
         // Distribution using weighted index sampling to determine the type of
-        // monster that is spawned
-        let monster_dist = WeightedIndex::new(&monster_weights).unwrap();
+        // monster that is spawned. There might not be any monsters for this level
+        let monster_dist = WeightedIndex::new(&monster_weights).ok();
 
         for _ in 0..num_monsters
         {
+            let monster_dist = match monster_dist.as_ref()
+            {
+                Some(dist) => dist,
+                None => break
+            };
+
             // Generate random position for monster
-            let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
-            let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
+            let x = rng.gen_range(room.x1 + 1, room.x2);
+            let y = rng.gen_range(room.y1 + 1, room.y2);
 
             if !self.is_blocked(x, y, objects)
             {
                 // Generate a monster based off of the weighted sample from our monster distribution
-                let mut monster = match monster_choices[monster_dist.sample(&mut rand::thread_rng())]
+                let mut monster = content.monsters[monster_dist.sample(rng)].spawn(x, y);
+
+                // Pack hunters that spawn in the same room hunt as one pack
+                if let Some(Ai::PackHunter { ref mut pack, .. }) = monster.ai
                 {
-                    "Orc" => 
-                    {
-                        let mut orc = Object::new(x, y, 'O', colors::DESATURATED_GREEN, "Orc", true);
-                        orc.fighter = Some(Fighter::new(2, 3, 2, 3, 2, 0, 0, 50, DeathCallback::MonsterDeath));
-                        orc.ai = Some(Ai::BasicMonster);
-                        orc
-                    },
+                    *pack = room_id;
+                }
 
-                    "Troll" => 
+                // Some monsters spawn wielding gear, which they drop when they die
+                if let Some(gear_dist) = gear_dist.as_ref()
+                {
+                    if rng.gen_range(0, 100) < monster_gear_chance
                     {
-                        let mut troll = Object::new(x, y, 'T', colors::DARKER_GREEN, "Troll", true);
-                        troll.fighter = Some(Fighter::new(5, 5, 5, 3, 3, 0, 0, 100, DeathCallback::MonsterDeath));
-                        troll.ai = Some(Ai::BasicMonster);
-                        troll
-                    },
-
-                    _ => unreachable!()
-                };
+                        let mut gear = content.items[gear_dist.sample(rng)].spawn(x, y);
+                        if let Some(equipment) = gear.equipment.as_mut()
+                        {
+                            equipment.equipped = true;
+                        }
+                        monster.inventory.push(gear);
+                    }
+                }
 
-                monster.alive = true;
-                objects.push(monster);
+                objects.insert(monster);
             }
         }
 
@@ -302,115 +362,25 @@ impl Map
         );
 
         // Generate number of items in the room [0, max]
-        let num_items = rand::thread_rng().gen_range(0, max_items + 1);
-
-        // The possible items that can be spawned
-        let item_choices = [ 
-            Item::HealthPotion, 
-            Item::Sword, 
-            Item::Shield, 
-            Item::PlateArmor 
-        ];
-
-        // The weights for each different type of item to spawn
-        let item_weights = [
-            // Health potion weight
-            35,
-
-            // Sword weight
-            from_dungeon_level(&[Transition{ level: 4, value: 25 }], dungeon_level),
-
-            // Shield weight
-            from_dungeon_level(&[Transition{ level: 5, value: 20 }], dungeon_level),
-
-            // Plate Armor weight
-            from_dungeon_level(&[Transition{ level: 3, value: 15 }], dungeon_level),
-        ];
-
-        // Distribution using weighted index sampling to determine the type of
-        // item that is spawned
-        let item_dist = WeightedIndex::new(&item_weights).unwrap();
+        let num_items = rng.gen_range(0, max_items + 1);
 
         for _ in 0..num_items
         {
+            let item_dist = match item_dist.as_ref()
+            {
+                Some(dist) => dist,
+                None => break
+            };
+
             // Generate a random position in the room for the item
-            let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
-            let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
+            let x = rng.gen_range(room.x1 + 1, room.x2);
+            let y = rng.gen_range(room.y1 + 1, room.y2);
 
             if !self.is_blocked(x, y, objects)
             {
-                let mut item = match item_choices[item_dist.sample(&mut rand::thread_rng())]
-                {
-                    Item::HealthPotion =>
-                    {
-                        let mut item = Object::new(x, y, '!', colors::LIGHT_VIOLET, "Health Potion", false);
-                        item.item = Some(Item::HealthPotion);
-                        item
-                    },
-
-                    Item::Sword =>
-                    {
-                        // TODO: figure out different tiers of sword
-                        let mut item = Object::new(x, y, '/', colors::BRASS, "Sword", false);
-                        item.item = Some(Item::Sword);
-                        item.equipment = Some(Equipment {
-                            slot: EquipmentSlot::RightHand,
-                            equipped: false,
-                            vit_bonus: 0,
-                            atk_bonus: 2,
-                            str_bonus: 2,
-                            def_bonus: 0,
-                            dex_bonus: 0,
-                            int_bonus: 0,
-                            lck_bonus: 0
-                        });
-
-                        item
-                    },
-
-                    Item::Shield =>
-                    {
-                        // TODO: figure out different tiers of shield
-                        let mut item = Object::new(x, y, '0', colors::BRASS, "Shield", false);
-                        item.item = Some(Item::Sword);
-                        item.equipment = Some(Equipment {
-                            slot: EquipmentSlot::RightHand,
-                            equipped: false,
-                            vit_bonus: 3,
-                            atk_bonus: 0,
-                            str_bonus: 0,
-                            def_bonus: 6,
-                            dex_bonus: 6,
-                            int_bonus: 0,
-                            lck_bonus: 0
-                        });
-
-                        item
-                    },
-
-                    Item::PlateArmor =>
-                    {
-                        // TODO: figure out different tiers of plate armor
-                        let mut item = Object::new(x, y, '#', colors::BRASS, "Plate Armor", false);
-                        item.item = Some(Item::Sword);
-                        item.equipment = Some(Equipment {
-                            slot: EquipmentSlot::RightHand,
-                            equipped: false,
-                            vit_bonus: 5,
-                            atk_bonus: 2,
-                            str_bonus: 2,
-                            def_bonus: 2,
-                            dex_bonus: 2,
-                            int_bonus: 0,
-                            lck_bonus: 5
-                        });
-
-                        item
-                    }
-                };
-
+                let mut item = content.items[item_dist.sample(rng)].spawn(x, y);
                 item.always_visible = true;
-                objects.push(item);
+                objects.insert(item);
             }
         }
     }
@@ -498,10 +468,11 @@ impl Rect
 
 /// Structure that associates a value with a level. Used for different values
 /// for things at different dungeon levels
-struct Transition
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Transition
 {
-    level: i32,
-    value: i32
+    pub level: i32,
+    pub value: i32
 }
 
 /// Returns a value that depends on the given level from the given table that
@@ -1,20 +1,53 @@
 use crate::{ TCOD, SCREEN_WIDTH, SCREEN_HEIGHT };
-use crate::game::{ self, Game, LEVEL_UP_BASE, LEVEL_UP_FACTOR, PLAYER_ID };
+use crate::game::{ Game, GameMode, LEVEL_UP_BASE, LEVEL_UP_FACTOR, PLAYER_ID };
 use crate::object::Object;
-use crate::fighter::Fighter;
+use crate::fighter::{ Fighter, Stat };
 use crate::item::*;
+use crate::replay;
+use crate::content;
+use crate::save::{ self, SaveError };
+use crate::keymap::{ self, Keymap, Command };
 
 use tcod::colors::{ self, Color };
 use tcod::console::*;
+use tcod::input::{ Key, KeyCode };
 
 const MAIN_MENU_WIDTH: i32 = 24;
 const INVENTORY_MENU_WIDTH: i32 = 50;
 const CHARACTER_MENU_WIDTH: i32 = 50;
 const LEVEL_UP_MENU_WIDTH: i32 = 40;
+const SLOT_MENU_WIDTH: i32 = 64;
+const HELP_MENU_WIDTH: i32 = 64;
+pub const MSG_BOX_WIDTH: i32 = 50;
+const NAME_MAX_LEN: usize = 20;
 
 pub fn main_menu(tcod: &mut TCOD)
 {
     let img = tcod::image::Image::from_file("./res/menu_background.png").ok().expect("Failed to load background image!");
+    let content = match content::load_content()
+    {
+        Ok(content) => content,
+        Err(e) =>
+        {
+            msg_box(&format!("Failed to load game content!\n\n{}", e), MSG_BOX_WIDTH, &mut tcod.root);
+            return;
+        }
+    };
+
+    match keymap::load_keymap()
+    {
+        Ok(keymap) => tcod.keymap = keymap,
+        Err(e) => msg_box(&format!("Failed to load the key bindings, using the defaults instead!\n\n{}", e), MSG_BOX_WIDTH, &mut tcod.root)
+    }
+
+    match save::import_legacy_save()
+    {
+        Ok(Some(slot)) => msg_box(&format!("Your old saved game was moved to save slot {}.", slot + 1), MSG_BOX_WIDTH, &mut tcod.root),
+        Ok(None) => {},
+        Err(SaveError::NoFreeSlot) => msg_box("Your old saved game couldn't be moved to a save slot since they're all taken. Delete a saved game and restart to import it.", MSG_BOX_WIDTH, &mut tcod.root),
+        Err(e) => msg_box(&format!("Failed to import your old saved game!\n\n{}", e), MSG_BOX_WIDTH, &mut tcod.root)
+    }
+
     while !tcod.root.window_closed()
     {
         // Draw background image & title/version text
@@ -24,40 +57,102 @@ pub fn main_menu(tcod: &mut TCOD)
         tcod.root.print_ex(SCREEN_WIDTH / 2, SCREEN_HEIGHT - 2, BackgroundFlag::None, TextAlignment::Center, "version 0.1.0");
 
         // Menu functionality
-        let opts = &["New Game", "Continue Game", "Exit"];
+        let opts = &["New Game", "Continue Game", "Watch Replay", "Exit"];
         let choice = menu("", opts, MAIN_MENU_WIDTH, colors::BLACK, 0.7, &mut tcod.root);
         match choice
         {
             // New Game
             Some(0) => 
             {
-                // Create a new game and start it
-                let mut game = Game::new();
+                // Pick the slot the game will be saved to and a name for the character
+                let slot = match slot_menu("Choose a slot for the new game:\n", true, &mut tcod.root)
+                {
+                    Some(slot) => slot,
+                    None => continue
+                };
+
+                let name = match text_input("What is your name?", &mut tcod.root)
+                {
+                    Some(ref name) if name.trim().is_empty() => "Player".to_string(),
+                    Some(name) => name.trim().to_string(),
+                    None => continue
+                };
+
+                let modes = &["Permadeath (your save is deleted when you die)", "Casual (keep your save when you die)"];
+                let mode = match menu("Choose a game mode:\n", modes, SLOT_MENU_WIDTH, colors::BLACK, 1.0, &mut tcod.root)
+                {
+                    Some(0) => GameMode::Permadeath,
+                    Some(1) => GameMode::Casual,
+                    _ => continue
+                };
+
+                // Create a new game with a random seed and start it
+                let mut game = Game::new(rand::random(), content.clone());
+                game.slot = slot;
+                game.objects[PLAYER_ID].name = name;
+                game.mode = mode;
                 game.start(tcod);
             }
 
             // Continue Game
             Some(1) =>
             {
-                match game::load_game()
+                let slot = match slot_menu("Choose a saved game:\n", false, &mut tcod.root)
+                {
+                    Some(slot) => slot,
+                    None => continue
+                };
+
+                let opts = &["Continue", "Delete", "Cancel"];
+                match menu(&format!("Slot {}\n", slot + 1), opts, MAIN_MENU_WIDTH, colors::BLACK, 1.0, &mut tcod.root)
                 {
-                    // If the game loaded successfully we can just start it
-                    Ok(mut game) =>
+                    // Continue
+                    Some(0) => match save::load_game(slot, content.clone())
                     {
-                        game.start(tcod);
-                    }
+                        // If the game loaded successfully we can just start it
+                        Ok(mut game) =>
+                        {
+                            game.start(tcod);
+                        }
+
+                        // If there was an error display the reason
+                        Err(e) =>
+                        {
+                            msg_box(&e.to_string(), MSG_BOX_WIDTH, &mut tcod.root);
+                            continue;
+                        }
+                    },
+
+                    // Delete
+                    Some(1) if confirm(&format!("Delete the saved game in slot {}?\n", slot + 1), &mut tcod.root) =>
+                    {
+                        if let Err(e) = save::delete_slot(slot)
+                        {
+                            msg_box(&e.to_string(), MSG_BOX_WIDTH, &mut tcod.root);
+                        }
+                    },
+
+                    _ => {}
+                }
+            }
+
+            // Watch Replay
+            Some(2) =>
+            {
+                match replay::load_replay()
+                {
+                    Ok(replay) => replay::play_replay(&replay, content.clone(), tcod),
 
-                    // If there was an error simply display a message
                     Err(_e) =>
                     {
-                        msg_box("No saved game to load!", MAIN_MENU_WIDTH, &mut tcod.root);
+                        msg_box("No replay to watch!", MAIN_MENU_WIDTH, &mut tcod.root);
                         continue;
                     }
                 }
             }
 
             // Exit
-            Some(2) =>
+            Some(3) =>
             {
                 break;
             }
@@ -84,21 +179,60 @@ pub fn character_menu(game: &Game, root: &mut Root)
         > Defense: {} ({})\n
         > Dexterity: {} ({})\n
         > Intelligence: {} ({})\n
-        > Luck: {} ({})\n
+        > Luck: {} ({})\n\n
+        Speed: {}\n
         ", 
         fighter.hp, fighter.max_hp, fighter.xp, xp_to_level, player.level, 
-        fighter.base_vit, player.vitality_value(&game.inventory),
-        fighter.base_atk, player.attack_value(&game.inventory),
-        fighter.base_str, player.strength_value(&game.inventory),
-        fighter.base_def, player.defense_value(&game.inventory),
-        fighter.base_dex, player.dexterity_value(&game.inventory),
-        fighter.base_int, player.intelligence_value(&game.inventory),
-        fighter.base_lck, player.luck_value(&game.inventory));
+        fighter.base_vit, player.vitality_value(),
+        fighter.base_atk, player.attack_value(),
+        fighter.base_str, player.strength_value(),
+        fighter.base_def, player.defense_value(),
+        fighter.base_dex, player.dexterity_value(),
+        fighter.base_int, player.intelligence_value(),
+        fighter.base_lck, player.luck_value(),
+        player.speed_value(true));
 
         msg_box(&msg, CHARACTER_MENU_WIDTH, root);
     }
 }
 
+/// Shows how the player's run ended
+pub fn death_screen(game: &Game, root: &mut Root)
+{
+    let player = &game.objects[PLAYER_ID];
+    let kills: Vec< String > = game.kill_summary().iter().map(|(name, count)| format!("{} x{}", name, count)).collect();
+    let save_note = match game.mode
+    {
+        GameMode::Permadeath => "Your save has been deleted.",
+        GameMode::Casual => "Your save has been kept, you can continue from when it was last saved."
+    };
+
+    let msg = format!("You have died!\n\n{}.\n\nDungeon level: {}\nCharacter level: {}\nTurns: {}\nKills: {}\n{}\n\n{}\n",
+        game.death_cause.as_ref().map_or("Killed by something", |c| c.as_str()),
+        game.dungeon_level,
+        player.level,
+        game.turns,
+        game.kills.len(),
+        kills.join(", "),
+        save_note);
+
+    msg_box(&msg, SLOT_MENU_WIDTH, root);
+}
+
+/// Lists every command along with the keys bound to it
+pub fn help_menu(keymap: &Keymap, root: &mut Root)
+{
+    let mut msg = "Controls:\n\n".to_string();
+    for command in Command::ALL.iter().cloned()
+    {
+        let keys = keymap.keys(command);
+        let keys = if keys.is_empty() { "(unbound)".to_string() } else { keys.join(", ") };
+        msg.push_str(&format!("{:<36}{}\n", command.description(), keys));
+    }
+
+    msg_box(&msg, HELP_MENU_WIDTH, root);
+}
+
 pub fn inventory_menu(inv: &[Object], header: &str, root: &mut Root) -> Option< usize >
 {
     let opts: Vec< String > = if inv.len() == 0
@@ -132,7 +266,7 @@ pub fn inventory_menu(inv: &[Object], header: &str, root: &mut Root) -> Option<
     }
 }
 
-pub fn level_up_menu(fighter: &Fighter, header: &str, root: &mut Root) -> Option< usize >
+pub fn level_up_menu(fighter: &Fighter, header: &str, root: &mut Root) -> Stat
 {
     let opts = &[
         format!("+1 Vitality ({} -> {})", fighter.base_vit, fighter.base_vit + 1),
@@ -150,7 +284,77 @@ pub fn level_up_menu(fighter: &Fighter, header: &str, root: &mut Root) -> Option
         choice = menu(header, opts, LEVEL_UP_MENU_WIDTH, colors::DARK_BLUE, 0.7, root);
     }
 
-    choice
+    Stat::ALL[choice.unwrap()]
+}
+
+/// Shows the save slots and returns the one picked. When picking a slot for a 
+/// new game empty slots can be picked too, and picking a used slot has to be
+/// confirmed since its saved game will be overwritten.
+fn slot_menu(header: &str, new_game: bool, root: &mut Root) -> Option< usize >
+{
+    let slots = save::list_slots();
+    if !new_game && slots.iter().all(|s| s.is_none())
+    {
+        msg_box("No saved game to load!", MAIN_MENU_WIDTH, root);
+        return None;
+    }
+
+    let opts: Vec< String > = slots.iter().enumerate().map(|(i, slot)| {
+        match *slot
+        {
+            Some(Some(ref meta)) => format!("Slot {}: {}", i + 1, meta),
+            Some(None) => format!("Slot {}: saved game", i + 1),
+            None => format!("Slot {}: empty", i + 1)
+        }
+    }).collect();
+
+    let choice = menu(header, &opts, SLOT_MENU_WIDTH, colors::BLACK, 1.0, root)?;
+    match (new_game, slots[choice].is_some())
+    {
+        (true, true) if !confirm(&format!("Overwrite the saved game in slot {}?\n", choice + 1), root) => None,
+        (false, false) => None,
+        _ => Some(choice)
+    }
+}
+
+/// Asks a yes or no question
+fn confirm(question: &str, root: &mut Root) -> bool
+{
+    menu(question, &["Yes", "No"], MSG_BOX_WIDTH, colors::BLACK, 1.0, root) == Some(0)
+}
+
+/// Lets the player type in a line of text. Returns None if they cancel with Escape.
+fn text_input(header: &str, root: &mut Root) -> Option< String >
+{
+    let width = MSG_BOX_WIDTH;
+    let height = 3;
+    let mut text = String::new();
+
+    while !root.window_closed()
+    {
+        let mut input_con = Offscreen::new(width, height);
+        input_con.set_default_background(colors::BLACK);
+        input_con.set_default_foreground(colors::WHITE);
+        input_con.clear();
+        input_con.print_ex(0, 0, BackgroundFlag::None, TextAlignment::Left, header);
+        input_con.print_ex(0, 2, BackgroundFlag::None, TextAlignment::Left, format!("> {}_", text));
+
+        let x = SCREEN_WIDTH / 2 - width / 2;
+        let y = SCREEN_HEIGHT / 2 - height / 2;
+        blit(&input_con, (0, 0), (width, height), root, (x, y), 1.0, 1.0);
+        root.flush();
+
+        match root.wait_for_keypress(true)
+        {
+            Key { code: KeyCode::Enter, .. } | Key { code: KeyCode::NumPadEnter, .. } => return Some(text),
+            Key { code: KeyCode::Escape, .. } => return None,
+            Key { code: KeyCode::Backspace, .. } => { text.pop(); },
+            Key { printable: c, .. } if (c.is_alphanumeric() || c == ' ' || c == '-') && text.chars().count() < NAME_MAX_LEN => text.push(c),
+            _ => {}
+        }
+    }
+
+    None
 }
 
 fn menu< T: AsRef< str > >(header: &str, opts: &[T], width: i32, background_color: Color, background_alpha: f32, root: &mut Root) -> Option< usize >
@@ -196,7 +400,7 @@ fn menu< T: AsRef< str > >(header: &str, opts: &[T], width: i32, background_colo
     }
 }
 
-fn msg_box(text: &str, width: i32, root: &mut Root)
+pub fn msg_box(text: &str, width: i32, root: &mut Root)
 {
     let opts: &[&str] = &[];
     menu(text, opts, width, colors::BLACK, 1.0, root);
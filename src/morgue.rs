@@ -0,0 +1,174 @@
+use crate::game::{ Game, GameMode, PLAYER_ID };
+use crate::paths;
+
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// The number of messages from the end of the log that go into a morgue file
+const MORGUE_MESSAGES: usize = 30;
+
+/// Represents how a run ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunEnd
+{
+    /// The player died
+    Death,
+
+    /// The player saved and quit
+    Quit
+}
+
+/// Writes a plain-text summary of the given run to the morgue directory and
+/// returns its path. Every run gets a single file (named after the character
+/// and the seed) that is overwritten each time the run ends, so a run that is
+/// saved and continued ends up with the summary of how it finally ended.
+pub fn write_morgue(game: &Game, end: RunEnd) -> io::Result< PathBuf >
+{
+    let dir = paths::data_dir().join("morgue");
+    fs::create_dir_all(&dir)?;
+
+    let player = &game.objects[PLAYER_ID];
+    let file_name: String = player.name.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect();
+    let path = dir.join(format!("{}-{}.txt", file_name, game.seed));
+    fs::write(&path, morgue_text(game, end))?;
+
+    Ok(path)
+}
+
+/// Builds the contents of the morgue file
+fn morgue_text(game: &Game, end: RunEnd) -> String
+{
+    let player = &game.objects[PLAYER_ID];
+    let mut text = String::new();
+
+    // Writing to a String can't fail, so the results are ignored
+    let outcome = match end
+    {
+        RunEnd::Death => game.death_cause.clone().unwrap_or_else(|| format!("Died on dungeon level {}", game.dungeon_level)),
+        RunEnd::Quit => format!("Saved and quit on dungeon level {}", game.dungeon_level)
+    };
+    let mode = match game.mode
+    {
+        GameMode::Permadeath => "permadeath",
+        GameMode::Casual => "casual"
+    };
+
+    let _ = writeln!(text, "roguelike-rs morgue file\n");
+    let _ = writeln!(text, "{}, level {}", player.name, player.level);
+    let _ = writeln!(text, "{}.", outcome);
+    let _ = writeln!(text, "Dungeon level: {}", game.dungeon_level);
+    let _ = writeln!(text, "Turns: {}", game.turns);
+    let _ = writeln!(text, "Seed: {} ({})", game.seed, mode);
+
+    // Final stats
+    let _ = writeln!(text, "\n== Stats ==\n");
+    if let Some(fighter) = player.fighter.as_ref()
+    {
+        let _ = writeln!(text, "HP: {}/{}", fighter.hp, fighter.max_hp);
+        let _ = writeln!(text, "XP: {}", fighter.xp);
+        let _ = writeln!(text, "Vitality: {} ({})", fighter.base_vit, player.vitality_value());
+        let _ = writeln!(text, "Attack: {} ({})", fighter.base_atk, player.attack_value());
+        let _ = writeln!(text, "Strength: {} ({})", fighter.base_str, player.strength_value());
+        let _ = writeln!(text, "Defense: {} ({})", fighter.base_def, player.defense_value());
+        let _ = writeln!(text, "Dexterity: {} ({})", fighter.base_dex, player.dexterity_value());
+        let _ = writeln!(text, "Intelligence: {} ({})", fighter.base_int, player.intelligence_value());
+        let _ = writeln!(text, "Luck: {} ({})", fighter.base_lck, player.luck_value());
+        let _ = writeln!(text, "Speed: {}", player.speed_value(true));
+        if !fighter.effects.is_empty()
+        {
+            let effects: Vec< String > = fighter.effects.iter().map(|e| format!("{} ({} turns)", e.kind.name(), e.turns)).collect();
+            let _ = writeln!(text, "Status: {}", effects.join(", "));
+        }
+    }
+
+    // Equipment and inventory
+    let _ = writeln!(text, "\n== Equipment ==\n");
+    let equipped = player.get_all_equipped();
+    if equipped.is_empty()
+    {
+        let _ = writeln!(text, "Nothing equipped");
+    }
+    for equipment in &equipped
+    {
+        let name = player.inventory.iter().find(|i| i.equipment.as_ref() == Some(equipment)).map_or("?", |i| i.name.as_str());
+        let _ = writeln!(text, "{}: {} ({})", equipment.slot, name, equipment.bonus_summary());
+    }
+
+    let _ = writeln!(text, "\n== Inventory ==\n");
+    if player.inventory.is_empty()
+    {
+        let _ = writeln!(text, "Empty");
+    }
+    for (i, item) in player.inventory.iter().enumerate()
+    {
+        let letter = (b'a' + i as u8) as char;
+        let equipped = if item.equipment.map_or(false, |e| e.equipped) { " (equipped)" } else { "" };
+        let _ = writeln!(text, "{}) {}{}", letter, item.name, equipped);
+    }
+
+    // Kills
+    let _ = writeln!(text, "\n== Kills ({}) ==\n", game.kills.len());
+    for (name, count) in game.kill_summary()
+    {
+        let _ = writeln!(text, "{:>4} {}", count, name);
+    }
+
+    // Last messages
+    let _ = writeln!(text, "\n== Last messages ==\n");
+    let first = game.log.len().saturating_sub(MORGUE_MESSAGES);
+    for (msg, _) in &game.log[first..]
+    {
+        let _ = writeln!(text, "{}", msg);
+    }
+
+    // The map as the player remembers it
+    let _ = writeln!(text, "\n== Map ==\n");
+    let _ = write!(text, "{}", map_dump(game));
+
+    text
+}
+
+/// Returns an ASCII picture of the explored part of the map along with the 
+/// objects the player can see or remembers
+fn map_dump(game: &Game) -> String
+{
+    let map = &game.map;
+    let mut rows: Vec< Vec< char > > = (0..map.height)
+        .map(|y| {
+            (0..map.width)
+                .map(|x| {
+                    let tile = &map.tiles[x as usize][y as usize];
+                    match (tile.explored, tile.blocked)
+                    {
+                        (false, _) => ' ',
+                        (true, true) => '#',
+                        (true, false) => '.'
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    // Solid objects are drawn last so they end up on top, like when rendering
+    let mut to_draw: Vec< _ > = game.objects
+        .values()
+        .filter(|o| map.in_bounds(o.pos) && (map.is_in_fov(o.pos) || (o.always_visible && map.is_explored(o.pos))))
+        .collect();
+    to_draw.sort_by_key(|o| o.solid);
+    for object in to_draw
+    {
+        rows[object.pos.1 as usize][object.pos.0 as usize] = object.c;
+    }
+
+    let mut dump = String::new();
+    for row in rows
+    {
+        let line: String = row.into_iter().collect();
+        dump.push_str(line.trim_end());
+        dump.push('\n');
+    }
+
+    dump
+}
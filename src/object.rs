@@ -1,13 +1,18 @@
 use crate::game::Game;
-use crate::fighter::Fighter;
+use crate::entity::ObjectId;
+use crate::fighter::{ Fighter, Stat, StatusKind };
 use crate::ai::Ai;
 use crate::item::{ Item, Equipment };
 use crate::gui::{ Messages, MessageLog };
+use crate::path::{ self, Walker };
 
 use tcod::colors::{ self, Color };
 use tcod::console::{ Console, BackgroundFlag };
 use std::cmp;
 
+/// Extra speed the player gains for every point of dexterity
+const DEX_SPEED_BONUS: i32 = 2;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Object
 {
@@ -22,7 +27,10 @@ pub struct Object
     pub fighter: Option< Fighter >,
     pub ai: Option< Ai >,
     pub item: Option< Item >,
-    pub equipment: Option< Equipment >
+    pub equipment: Option< Equipment >,
+
+    /// Everything this object is carrying, including its equipped gear
+    pub inventory: Vec< Object >
 }
 
 impl Object
@@ -42,7 +50,8 @@ impl Object
             fighter: None,
             ai: None,
             item: None,
-            equipment: None
+            equipment: None,
+            inventory: vec![]
         }
     }
 
@@ -86,14 +95,16 @@ impl Object
         }
     }
 
-    /// Function to make this object attack a different target object
-    pub fn attack(&mut self, target: &mut Object, inv: &[Object], log: &mut Messages)
+    /// Function to make this object attack a different target object. Returns
+    /// true if the attack killed the target.
+    pub fn attack(&mut self, target: &mut Object, log: &mut Messages) -> bool
     {
-        // TODO: figure out hit chance and damage formulas
-        let hit_chance = self.attack_value(inv) as f32 / target.dexterity_value(inv) as f32;
-        let damage = if hit_chance > 0.5 
+        // The attack lands if the attacker's attack is more than half of the
+        // target's dexterity
+        let hit_chance = self.attack_value() as f32 / cmp::max(1, target.dexterity_value()) as f32;
+        let damage = if hit_chance > 0.5
         {
-            self.strength_value(inv) - target.defense_value(inv)
+            self.strength_value() - target.defense_value()
         }
         else
         {
@@ -107,12 +118,15 @@ impl Object
             if let Some(xp) = target.take_damage(damage, log)
             {
                 self.fighter.as_mut().unwrap().xp += xp;
+                return true;
             }
         }
         else
         {
             log.add(format!("{} attacks {} but it has no effect!", self.name, target.name), colors::WHITE);
         }
+
+        false
     }
 
     /// Function to make this object take the given amount of damage
@@ -126,13 +140,13 @@ impl Object
             }
         }
 
-        if let Some(fighter) = self.fighter
+        if let Some((hp, xp, on_death)) = self.fighter.as_ref().map(|f| (f.hp, f.xp, f.on_death))
         {
-            if fighter.hp <= 0
+            if hp <= 0
             {
                 self.alive = false;
-                fighter.on_death.callback(self, log);
-                return Some(fighter.xp);
+                on_death.callback(self, log);
+                return Some(xp);
             }
         }
 
@@ -186,9 +200,9 @@ impl Object
     }
 
     /// Function to get all items equipped to this object
-    pub fn get_all_equipped(&self, inv: &[Object]) -> Vec< Equipment >
+    pub fn get_all_equipped(&self) -> Vec< Equipment >
     {
-        inv
+        self.inventory
             .iter()
             .filter(|item| { 
                 item.equipment.map_or(false, |e| e.equipped) 
@@ -197,75 +211,106 @@ impl Object
             .collect()
     }
 
-    pub fn vitality_value(&self, inv: &[Object]) -> i32
+    pub fn vitality_value(&self) -> i32
     {
-        let base = self.fighter.map_or(0, |f| f.base_vit);
-        let bonus = self.get_all_equipped(inv).iter().fold(0, |sum, e| sum + e.vit_bonus);
+        let base = self.fighter.as_ref().map_or(0, |f| f.base_vit);
+        let bonus = self.get_all_equipped().iter().fold(0, |sum, e| sum + e.vit_bonus);
+        let effects = self.fighter.as_ref().map_or(0, |f| f.effect_modifier(Stat::Vitality));
 
-        base + bonus
+        base + bonus + effects
     }
 
-    pub fn attack_value(&self, inv: &[Object]) -> i32
+    pub fn attack_value(&self) -> i32
     {
-        let base = self.fighter.map_or(0, |f| f.base_atk);
-        let bonus = self.get_all_equipped(inv).iter().fold(0, |sum, e| sum + e.atk_bonus);
+        let base = self.fighter.as_ref().map_or(0, |f| f.base_atk);
+        let bonus = self.get_all_equipped().iter().fold(0, |sum, e| sum + e.atk_bonus);
+        let effects = self.fighter.as_ref().map_or(0, |f| f.effect_modifier(Stat::Attack));
 
-        base + bonus
+        base + bonus + effects
     }
 
-    pub fn strength_value(&self, inv: &[Object]) -> i32
+    pub fn strength_value(&self) -> i32
     {
-        let base = self.fighter.map_or(0, |f| f.base_str);
-        let bonus = self.get_all_equipped(inv).iter().fold(0, |sum, e| sum + e.str_bonus);
+        let base = self.fighter.as_ref().map_or(0, |f| f.base_str);
+        let bonus = self.get_all_equipped().iter().fold(0, |sum, e| sum + e.str_bonus);
+        let effects = self.fighter.as_ref().map_or(0, |f| f.effect_modifier(Stat::Strength));
 
-        base + bonus
+        base + bonus + effects
     }
 
-    pub fn defense_value(&self, inv: &[Object]) -> i32
+    pub fn defense_value(&self) -> i32
     {
-        let base = self.fighter.map_or(0, |f| f.base_def);
-        let bonus = self.get_all_equipped(inv).iter().fold(0, |sum, e| sum + e.def_bonus);
+        let base = self.fighter.as_ref().map_or(0, |f| f.base_def);
+        let bonus = self.get_all_equipped().iter().fold(0, |sum, e| sum + e.def_bonus);
+        let effects = self.fighter.as_ref().map_or(0, |f| f.effect_modifier(Stat::Defense));
 
-        base + bonus
+        base + bonus + effects
     }
 
-    pub fn dexterity_value(&self, inv: &[Object]) -> i32
+    pub fn dexterity_value(&self) -> i32
     {
-        let base = self.fighter.map_or(0, |f| f.base_dex);
-        let bonus = self.get_all_equipped(inv).iter().fold(0, |sum, e| sum + e.dex_bonus);
+        let base = self.fighter.as_ref().map_or(0, |f| f.base_dex);
+        let bonus = self.get_all_equipped().iter().fold(0, |sum, e| sum + e.dex_bonus);
+        let effects = self.fighter.as_ref().map_or(0, |f| f.effect_modifier(Stat::Dexterity));
 
-        base + bonus
+        base + bonus + effects
     }
 
-    pub fn intelligence_value(&self, inv: &[Object]) -> i32
+    /// Returns the energy this object gains every tick. Every point of dexterity
+    /// makes the player (`is_player`) a little quicker, while monsters keep the
+    /// speed of their template. Being hasted or slowed doubles or halves it.
+    pub fn speed_value(&self, is_player: bool) -> i32
     {
-        let base = self.fighter.map_or(0, |f| f.base_int);
-        let bonus = self.get_all_equipped(inv).iter().fold(0, |sum, e| sum + e.int_bonus);
+        let base = self.fighter.as_ref().map_or(0, |f| f.speed);
+        let bonus = if is_player { self.dexterity_value() * DEX_SPEED_BONUS } else { 0 };
+        let mut speed = base + bonus;
 
-        base + bonus
+        if let Some(fighter) = self.fighter.as_ref()
+        {
+            if fighter.has_effect(StatusKind::Haste)
+            {
+                speed *= 2;
+            }
+            if fighter.has_effect(StatusKind::Slow)
+            {
+                speed /= 2;
+            }
+        }
+
+        cmp::max(1, speed)
+    }
+
+    pub fn intelligence_value(&self) -> i32
+    {
+        let base = self.fighter.as_ref().map_or(0, |f| f.base_int);
+        let bonus = self.get_all_equipped().iter().fold(0, |sum, e| sum + e.int_bonus);
+        let effects = self.fighter.as_ref().map_or(0, |f| f.effect_modifier(Stat::Intelligence));
+
+        base + bonus + effects
     }
 
-    pub fn luck_value(&self, inv: &[Object]) -> i32
+    pub fn luck_value(&self) -> i32
     {
-        let base = self.fighter.map_or(0, |f| f.base_lck);
-        let bonus = self.get_all_equipped(inv).iter().fold(0, |sum, e| sum + e.lck_bonus);
+        let base = self.fighter.as_ref().map_or(0, |f| f.base_lck);
+        let bonus = self.get_all_equipped().iter().fold(0, |sum, e| sum + e.lck_bonus);
+        let effects = self.fighter.as_ref().map_or(0, |f| f.effect_modifier(Stat::Luck));
 
-        base + bonus
+        base + bonus + effects
     }
 }
 
 /// Function to move an object by the given delta X and delta Y
-pub fn move_by(id: usize, dx: i32, dy: i32, game: &mut Game)
+pub fn move_by(id: ObjectId, dx: i32, dy: i32, game: &mut Game)
 {
     let (x, y) = game.objects[id].pos;
-    if !game.map.is_blocked(x + dx, y + dy, &game.objects)
+    if !game.map.is_blocked(x + dx, y + dy, &game.objects) && !game.map.cuts_corner((x, y), dx, dy)
     {
         game.objects[id].set_pos(x + dx, y + dy);
     }
 }
 
 /// Function to move an object towards the target (x, y) position
-pub fn move_towards(id: usize, target_x: i32, target_y: i32, game: &mut Game)
+pub fn move_towards(id: ObjectId, target_x: i32, target_y: i32, game: &mut Game)
 {
     let dx = target_x - game.objects[id].pos.0;
     let dy = target_y - game.objects[id].pos.1;
@@ -277,19 +322,19 @@ pub fn move_towards(id: usize, target_x: i32, target_y: i32, game: &mut Game)
     move_by(id, dx, dy, game);
 }
 
-/// Mutably borrow two *separate* elements from the given slice.
-/// Panics when the indexes are equal or out of bounds.
-pub fn mut_two< T >(first_index: usize, second_index: usize, items: &mut [T]) -> (&mut T, &mut T) 
+/// Function to move an object one step along the shortest path to the target
+/// (x, y) position. Paths go around other monsters where possible, otherwise
+/// the object waits behind them. Falls back to `move_towards` if there's no
+/// path at all.
+pub fn path_towards(id: ObjectId, target_x: i32, target_y: i32, game: &mut Game)
 {
-    assert!(first_index != second_index);
-    let split_at_index = cmp::max(first_index, second_index);
-    let (first_slice, second_slice) = items.split_at_mut(split_at_index);
-    if first_index < second_index 
-    {
-        (&mut first_slice[first_index], &mut second_slice[0])
-    } 
-    else 
+    let pos = game.objects[id].pos;
+    let path = path::find_path(&game.map, &game.objects, pos, (target_x, target_y), Walker::Monster { monsters_passable: false })
+        .or_else(|| path::find_path(&game.map, &game.objects, pos, (target_x, target_y), Walker::Monster { monsters_passable: true }));
+
+    match path.as_ref().and_then(|p| p.first())
     {
-        (&mut second_slice[0], &mut first_slice[second_index])
+        Some(&(x, y)) => move_by(id, x - pos.0, y - pos.1, game),
+        None => move_towards(id, target_x, target_y, game)
     }
-}
\ No newline at end of file
+}
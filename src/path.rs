@@ -0,0 +1,419 @@
+use crate::map::Map;
+use crate::entity::Objects;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// The cost of stepping onto a tile, orthogonally or diagonally
+const STEP_COST: i32 = 1;
+
+/// The extra cost of stepping onto a tile with a monster on it when monsters
+/// are passable, so paths still go around them when that isn't much longer
+const OCCUPIED_COST: i32 = 5;
+
+/// How strongly a flee map prefers getting far away over getting away
+/// quickly, in tenths. Above 10 makes fleeing things run past the nearest dead
+/// end towards open space.
+const FLEE_FACTOR: i32 = 12;
+
+/// Every direction a path can step in. The order matters for breaking ties,
+/// so that the same map always gives the same path.
+const DIRECTIONS: [(i32, i32); 8] = [(0, -1), (0, 1), (-1, 0), (1, 0), (-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+/// Who a path is worked out for, which decides what stands in its way
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Walker
+{
+    /// A monster, which knows the whole map. When `monsters_passable` is set,
+    /// tiles with other monsters on them can be walked through but cost more.
+    Monster { monsters_passable: bool },
+
+    /// The player, who can only plan a path over explored tiles and around
+    /// the monsters they can see
+    Player
+}
+
+/// The cost of walking over every tile of the map, worked out once per search
+struct CostGrid
+{
+    width: i32,
+    height: i32,
+
+    /// None for tiles that can't be walked through
+    costs: Vec< Option< i32 > >
+}
+
+impl CostGrid
+{
+    /// Builds the costs from the tiles and the solid objects on the map, as far
+    /// as the walker knows about them
+    fn new(map: &Map, objects: &Objects, walker: Walker) -> Self
+    {
+        let player = walker == Walker::Player;
+        let mut costs: Vec< Option< i32 > > = (0..map.width * map.height)
+            .map(|i| {
+                let tile = &map.tiles[(i % map.width) as usize][(i / map.width) as usize];
+                if tile.blocked || (player && !tile.explored) { None } else { Some(STEP_COST) }
+            })
+            .collect();
+
+        for object in objects.values().filter(|o| o.solid && map.in_bounds(o.pos) && (!player || map.is_in_fov(o.pos)))
+        {
+            let cost = &mut costs[(object.pos.1 * map.width + object.pos.0) as usize];
+            *cost = match (*cost, walker)
+            {
+                (Some(c), Walker::Monster { monsters_passable: true }) => Some(c + OCCUPIED_COST),
+                _ => None
+            };
+        }
+
+        CostGrid { width: map.width, height: map.height, costs: costs }
+    }
+
+    fn index(&self, pos: (i32, i32)) -> usize
+    {
+        (pos.1 * self.width + pos.0) as usize
+    }
+
+    /// Returns the cost of stepping onto the given position, None if it can't be
+    /// stepped onto. Positions in `allowed` can always be stepped onto, unless
+    /// they're walls.
+    fn cost(&self, map: &Map, pos: (i32, i32), allowed: &[(i32, i32)]) -> Option< i32 >
+    {
+        if !map.in_bounds(pos) || map.tiles[pos.0 as usize][pos.1 as usize].blocked
+        {
+            None
+        }
+        else if allowed.contains(&pos)
+        {
+            Some(STEP_COST)
+        }
+        else
+        {
+            self.costs[self.index(pos)]
+        }
+    }
+
+    /// Returns every position that can be stepped to from the given one along
+    /// with the cost of the step
+    fn neighbours(&self, map: &Map, pos: (i32, i32), allowed: &[(i32, i32)]) -> Vec< ((i32, i32), i32) >
+    {
+        DIRECTIONS
+            .iter()
+            .filter(|(dx, dy)| !map.cuts_corner(pos, *dx, *dy))
+            .filter_map(|(dx, dy)| {
+                let next = (pos.0 + dx, pos.1 + dy);
+                self.cost(map, next, allowed).map(|c| (next, c))
+            })
+            .collect()
+    }
+}
+
+/// Finds the cheapest path between two positions using A*. The returned path
+/// doesn't include the start but does include the goal, which can always be
+/// walked onto even if something is standing on it (e.g. the player being
+/// chased). Returns None if the goal can't be reached.
+pub fn find_path(map: &Map, objects: &Objects, from: (i32, i32), to: (i32, i32), walker: Walker) -> Option< Vec< (i32, i32) > >
+{
+    if !map.in_bounds(from) || !map.in_bounds(to)
+    {
+        return None;
+    }
+
+    let grid = CostGrid::new(map, objects, walker);
+    let allowed = [to];
+    let heuristic = |pos: (i32, i32)| step_distance(pos, to) * STEP_COST;
+
+    let mut costs = vec![i32::MAX; (grid.width * grid.height) as usize];
+    let mut came_from: Vec< Option< (i32, i32) > > = vec![None; costs.len()];
+    let mut open = BinaryHeap::new();
+
+    costs[grid.index(from)] = 0;
+    open.push(Reverse((heuristic(from), 0, from)));
+
+    while let Some(Reverse((_, cost, pos))) = open.pop()
+    {
+        if pos == to
+        {
+            // Walk back from the goal to rebuild the path
+            let mut path = vec![to];
+            let mut current = to;
+            while let Some(previous) = came_from[grid.index(current)]
+            {
+                if previous == from
+                {
+                    break;
+                }
+                path.push(previous);
+                current = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        // Skip stale entries for positions that were already reached more cheaply
+        if cost > costs[grid.index(pos)]
+        {
+            continue;
+        }
+
+        for (next, step_cost) in grid.neighbours(map, pos, &allowed)
+        {
+            let next_cost = cost + step_cost;
+            let index = grid.index(next);
+            if next_cost < costs[index]
+            {
+                costs[index] = next_cost;
+                came_from[index] = Some(pos);
+                open.push(Reverse((next_cost + heuristic(next), next_cost, next)));
+            }
+        }
+    }
+
+    None
+}
+
+/// A map of how far every tile is from the nearest of a set of goals. Walking
+/// "downhill" from any tile leads to the closest goal, which makes it cheap to
+/// path many things towards the same goals (or away from them).
+pub struct DijkstraMap
+{
+    width: i32,
+
+    /// The distance to the nearest goal, None for tiles that can't reach one
+    values: Vec< Option< i32 > >
+}
+
+impl DijkstraMap
+{
+    /// Builds a map of the distances to the given goals. Goals can always be
+    /// walked onto, even if something is standing on them.
+    pub fn new(map: &Map, objects: &Objects, goals: &[(i32, i32)], walker: Walker) -> Self
+    {
+        let seeds: Vec< ((i32, i32), i32) > = goals.iter().map(|&goal| (goal, 0)).collect();
+        DijkstraMap::from_seeds(map, objects, &seeds, goals, walker)
+    }
+
+    /// Builds a map for running away from the given positions: walking
+    /// downhill leads away from them, preferring open space over nearby dead
+    /// ends.
+    pub fn flee(map: &Map, objects: &Objects, from: &[(i32, i32)], walker: Walker) -> Self
+    {
+        let towards = DijkstraMap::new(map, objects, from, walker);
+        let seeds: Vec< ((i32, i32), i32) > = towards.values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| v.map(|v| ((i as i32 % towards.width, i as i32 / towards.width), -v * FLEE_FACTOR / 10)))
+            .collect();
+
+        DijkstraMap::from_seeds(map, objects, &seeds, from, walker)
+    }
+
+    /// Builds a map of the distances from the given starting values, spreading
+    /// out to every tile that can be walked to. Positions in `allowed` can
+    /// always be walked onto.
+    fn from_seeds(map: &Map, objects: &Objects, seeds: &[((i32, i32), i32)], allowed: &[(i32, i32)], walker: Walker) -> Self
+    {
+        let grid = CostGrid::new(map, objects, walker);
+        let mut values: Vec< Option< i32 > > = vec![None; (grid.width * grid.height) as usize];
+        let mut open = BinaryHeap::new();
+
+        for &(pos, value) in seeds.iter().filter(|(pos, _)| map.in_bounds(*pos))
+        {
+            let index = grid.index(pos);
+            if values[index].map_or(true, |v| value < v)
+            {
+                values[index] = Some(value);
+                open.push(Reverse((value, pos)));
+            }
+        }
+
+        while let Some(Reverse((cost, pos))) = open.pop()
+        {
+            if values[grid.index(pos)].map_or(false, |v| cost > v)
+            {
+                continue;
+            }
+
+            for (next, step_cost) in grid.neighbours(map, pos, allowed)
+            {
+                let next_cost = cost + step_cost;
+                let index = grid.index(next);
+                if values[index].map_or(true, |v| next_cost < v)
+                {
+                    values[index] = Some(next_cost);
+                    open.push(Reverse((next_cost, next)));
+                }
+            }
+        }
+
+        DijkstraMap { width: grid.width, values: values }
+    }
+
+    /// Returns the distance from the given position to the nearest goal, None
+    /// if no goal can be reached from it
+    pub fn value(&self, pos: (i32, i32)) -> Option< i32 >
+    {
+        if pos.0 < 0 || pos.1 < 0 || pos.0 >= self.width
+        {
+            return None;
+        }
+        self.values.get((pos.1 * self.width + pos.0) as usize).cloned().unwrap_or(None)
+    }
+
+    /// Returns the neighbouring position that gets closest to a goal from the
+    /// given position, None if already on a goal or none can be reached. The
+    /// given position itself doesn't need a value, since whatever is walking
+    /// usually stands in the way of its own tile.
+    pub fn next_step(&self, map: &Map, pos: (i32, i32)) -> Option< (i32, i32) >
+    {
+        let current = self.value(pos).unwrap_or(i32::MAX);
+        DIRECTIONS
+            .iter()
+            .filter(|(dx, dy)| !map.cuts_corner(pos, *dx, *dy))
+            .map(|(dx, dy)| (pos.0 + dx, pos.1 + dy))
+            .filter_map(|next| self.value(next).map(|v| (v, next)))
+            .filter(|(v, _)| *v < current)
+            .min_by_key(|(v, _)| *v)
+            .map(|(_, next)| next)
+    }
+}
+
+/// Returns the number of steps between two positions on an open map, where
+/// diagonal steps cost the same as orthogonal ones
+pub fn step_distance(a: (i32, i32), b: (i32, i32)) -> i32
+{
+    (a.0 - b.0).abs().max((a.1 - b.1).abs())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::map::{ MAP_WIDTH, MAP_HEIGHT };
+    use crate::object::Object;
+
+    use tcod::colors;
+
+    const MONSTER: Walker = Walker::Monster { monsters_passable: false };
+
+    /// An open map with a wall along x = 5 from the top down to y = 8
+    fn walled_map() -> Map
+    {
+        let mut map = Map::new();
+        for y in 0..=8
+        {
+            map.tiles[5][y].blocked = true;
+        }
+
+        map
+    }
+
+    /// Checks that every step of the path is a single, legal step
+    fn assert_walkable(map: &Map, from: (i32, i32), path: &[(i32, i32)])
+    {
+        let mut pos = from;
+        for &next in path
+        {
+            assert_eq!(step_distance(pos, next), 1);
+            assert!(!map.tiles[next.0 as usize][next.1 as usize].blocked);
+            assert!(!map.cuts_corner(pos, next.0 - pos.0, next.1 - pos.1));
+            pos = next;
+        }
+    }
+
+    #[test]
+    fn paths_go_around_walls()
+    {
+        let map = walled_map();
+        let path = find_path(&map, &Objects::new(), (3, 5), (7, 5), MONSTER).expect("No path around the wall!");
+
+        assert_eq!(path.last(), Some(&(7, 5)));
+        assert!(path.len() as i32 > step_distance((3, 5), (7, 5)));
+        assert_walkable(&map, (3, 5), &path);
+
+        let dijkstra = DijkstraMap::new(&map, &Objects::new(), &[(7, 5)], MONSTER);
+        assert_eq!(dijkstra.value((3, 5)), Some(path.len() as i32));
+        let mut pos = (3, 5);
+        while let Some(next) = dijkstra.next_step(&map, pos)
+        {
+            assert_walkable(&map, pos, &[next]);
+            pos = next;
+        }
+        assert_eq!(pos, (7, 5));
+    }
+
+    #[test]
+    fn walled_in_goals_are_unreachable()
+    {
+        let mut map = Map::new();
+        for &(dx, dy) in DIRECTIONS.iter()
+        {
+            map.tiles[(10 + dx) as usize][(10 + dy) as usize].blocked = true;
+        }
+
+        assert_eq!(find_path(&map, &Objects::new(), (3, 3), (10, 10), MONSTER), None);
+
+        let dijkstra = DijkstraMap::new(&map, &Objects::new(), &[(10, 10)], MONSTER);
+        assert_eq!(dijkstra.value((3, 3)), None);
+        assert_eq!(dijkstra.next_step(&map, (3, 3)), None);
+    }
+
+    #[test]
+    fn paths_dont_cut_wall_corners()
+    {
+        let mut map = Map::new();
+        map.tiles[5][4].blocked = true;
+
+        let path = find_path(&map, &Objects::new(), (4, 4), (5, 5), MONSTER).expect("No path past the corner!");
+        assert_eq!(path, vec![(4, 5), (5, 5)]);
+
+        let dijkstra = DijkstraMap::new(&map, &Objects::new(), &[(5, 5)], MONSTER);
+        assert_eq!(dijkstra.next_step(&map, (4, 4)), Some((4, 5)));
+    }
+
+    #[test]
+    fn paths_from_the_map_edge_stay_on_the_map()
+    {
+        let map = Map::new();
+        let corners = [(0, 0), (MAP_WIDTH - 1, 0), (0, MAP_HEIGHT - 1), (MAP_WIDTH - 1, MAP_HEIGHT - 1)];
+        for &corner in corners.iter()
+        {
+            let goal = (corner.0.clamp(3, MAP_WIDTH - 4), corner.1.clamp(3, MAP_HEIGHT - 4));
+            let path = find_path(&map, &Objects::new(), corner, goal, MONSTER).expect("No path from the corner!");
+            assert_eq!(path.len() as i32, step_distance(corner, goal));
+            assert_walkable(&map, corner, &path);
+
+            let dijkstra = DijkstraMap::new(&map, &Objects::new(), &[goal], MONSTER);
+            assert_eq!(dijkstra.next_step(&map, corner), path.first().cloned());
+
+            let flee = DijkstraMap::flee(&map, &Objects::new(), &[goal], MONSTER);
+            assert!(flee.next_step(&map, corner).map_or(true, |next| map.in_bounds(next)));
+        }
+    }
+
+    #[test]
+    fn player_paths_only_use_what_the_player_knows()
+    {
+        let mut map = Map::new();
+        for x in 0..MAP_WIDTH
+        {
+            map.tiles[x as usize][5].explored = true;
+        }
+
+        // Nothing is in the player's FOV, so the monster in the way is unseen
+        let mut objects = Objects::new();
+        objects.insert(Object::new(5, 5, 'o', colors::WHITE, "orc", true));
+
+        let path = find_path(&map, &objects, (3, 5), (7, 5), Walker::Player).expect("No path along the explored row!");
+        assert_eq!(path, vec![(4, 5), (5, 5), (6, 5), (7, 5)]);
+
+        // Monsters know about each other and about the unexplored tiles
+        let path = find_path(&map, &objects, (3, 5), (7, 5), MONSTER).expect("No path around the monster!");
+        assert_eq!(path.len(), 4);
+        assert!(!path.contains(&(5, 5)));
+
+        map.tiles[5][5].explored = false;
+        assert_eq!(find_path(&map, &objects, (3, 5), (7, 5), Walker::Player), None);
+    }
+}
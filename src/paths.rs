@@ -0,0 +1,44 @@
+use std::env;
+use std::path::PathBuf;
+
+const APP_NAME: &str = "roguelike-rs";
+
+/// Returns the per-user directory the game keeps its data (saves etc.) in:
+/// `%APPDATA%\roguelike-rs` on Windows and `$XDG_DATA_HOME/roguelike-rs` or
+/// `~/.local/share/roguelike-rs` everywhere else. Falls back to the working
+/// directory if none of those are set. The directory isn't created.
+pub fn data_dir() -> PathBuf
+{
+    let base = if cfg!(windows)
+    {
+        env::var_os("APPDATA").map(PathBuf::from)
+    }
+    else
+    {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+    };
+
+    base.map_or_else(|| PathBuf::from("."), |base| base.join(APP_NAME))
+}
+
+/// Returns the per-user directory the game keeps its configuration in:
+/// `%APPDATA%\roguelike-rs` on Windows and `$XDG_CONFIG_HOME/roguelike-rs` or
+/// `~/.config/roguelike-rs` everywhere else. Falls back to the working
+/// directory if none of those are set. The directory isn't created.
+pub fn config_dir() -> PathBuf
+{
+    let base = if cfg!(windows)
+    {
+        env::var_os("APPDATA").map(PathBuf::from)
+    }
+    else
+    {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+
+    base.map_or_else(|| PathBuf::from("."), |base| base.join(APP_NAME))
+}
@@ -0,0 +1,172 @@
+use crate::{ TCOD, SCREEN_WIDTH };
+use crate::game::{ Game, PlayerAction };
+use crate::content::Content;
+use crate::paths;
+
+use tcod::colors;
+use tcod::console::*;
+use tcod::input::{ Key, KeyCode, Event };
+
+use std::error::Error;
+use std::fs::{ self, File };
+use std::io::{ Read, Write };
+use std::path::PathBuf;
+
+/// The replay file of the latest run, kept in the data directory. The first
+/// line holds the seed and every line after it one action, so the file can be
+/// appended to as the run goes.
+const REPLAY_FILE: &str = "replay.jsonl";
+
+/// The number of frames to wait between actions at each playback speed
+const PLAYBACK_SPEEDS: [i32; 5] = [20, 10, 5, 2, 1];
+
+/// A recording of a run: the seed the game was created with plus every action
+/// that was fed into it. Since all of the game's randomness comes from the
+/// seed, replaying the actions reproduces the run exactly.
+#[derive(Serialize, Deserialize)]
+pub struct Replay
+{
+    pub seed: u64,
+    pub actions: Vec< PlayerAction >
+}
+
+/// The first line of the replay file
+#[derive(Serialize, Deserialize)]
+struct ReplayHeader
+{
+    seed: u64
+}
+
+/// Writes a game's replay to the replay file while it's being played, one
+/// action at a time, so a crash doesn't lose the run
+pub struct ReplayRecorder
+{
+    file: File,
+
+    /// The number of the game's actions already in the file
+    written: usize
+}
+
+impl ReplayRecorder
+{
+    /// Starts the replay file over with everything that has happened in the
+    /// given game so far
+    pub fn create(game: &Game) -> Result< Self, Box< dyn Error > >
+    {
+        fs::create_dir_all(paths::data_dir())?;
+        let mut file = File::create(replay_path())?;
+        writeln!(file, "{}", serde_json::to_string(&ReplayHeader { seed: game.seed })?)?;
+
+        let mut recorder = ReplayRecorder { file: file, written: 0 };
+        recorder.record(game)?;
+
+        Ok(recorder)
+    }
+
+    /// Appends the actions the game has taken since the last call
+    pub fn record(&mut self, game: &Game) -> Result< (), Box< dyn Error > >
+    {
+        for action in &game.actions[self.written..]
+        {
+            writeln!(self.file, "{}", serde_json::to_string(action)?)?;
+        }
+        self.written = game.actions.len();
+        self.file.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Deserializes the replay file. A run that crashed while an action was being
+/// written leaves half a line at the end, which is left out.
+pub fn load_replay() -> Result< Replay, Box< dyn Error > >
+{
+    let mut json_replay = String::new();
+    let mut file = File::open(replay_path())?;
+    file.read_to_string(&mut json_replay)?;
+
+    let mut lines = json_replay.lines();
+    let header = serde_json::from_str::< ReplayHeader >(lines.next().ok_or("the replay is empty")?)?;
+    let lines: Vec< &str > = lines.collect();
+    let mut actions = vec![];
+    for (i, line) in lines.iter().enumerate()
+    {
+        match serde_json::from_str::< PlayerAction >(line)
+        {
+            Ok(action) => actions.push(action),
+            Err(_) if i == lines.len() - 1 => break,
+            Err(e) => return Err(e.into())
+        }
+    }
+
+    Ok(Replay { seed: header.seed, actions: actions })
+}
+
+fn replay_path() -> PathBuf
+{
+    paths::data_dir().join(REPLAY_FILE)
+}
+
+/// Plays back the given replay. The run is only reproduced exactly if the
+/// content files haven't changed since it was recorded. Space pauses/resumes,
+/// '.' steps a single action while paused, '+' and '-' change the playback
+/// speed and Escape stops watching.
+pub fn play_replay(replay: &Replay, content: Content, tcod: &mut TCOD)
+{
+    let mut game = Game::new(replay.seed, content);
+    let mut next_action = 0;
+    let mut paused = false;
+    let mut speed = 2;
+    let mut frames = 0;
+
+    while !tcod.root.window_closed()
+    {
+        // Check for tcod input events
+        let mut step = false;
+        match tcod::input::check_for_event(tcod::input::MOUSE | tcod::input::KEY_PRESS)
+        {
+            Some((_, Event::Mouse(m))) => tcod.mouse = m,
+
+            Some((_, Event::Key(key))) => match key
+            {
+                Key { code: KeyCode::Escape, .. } => break,
+                Key { code: KeyCode::Spacebar, .. } => paused = !paused,
+                Key { printable: '.', .. } => step = paused,
+                Key { printable: '+', .. } | Key { code: KeyCode::NumPadAdd, .. } => speed = (speed + 1).min(PLAYBACK_SPEEDS.len() - 1),
+                Key { printable: '-', .. } | Key { code: KeyCode::NumPadSubtract, .. } => speed = speed.saturating_sub(1),
+                _ => {}
+            },
+
+            _ => {}
+        }
+
+        // Feed the next recorded action into the game when it's due
+        frames += 1;
+        if next_action < replay.actions.len() && (step || (!paused && frames >= PLAYBACK_SPEEDS[speed]))
+        {
+            game.step(replay.actions[next_action]);
+            next_action += 1;
+            frames = 0;
+        }
+
+        // Render the game with the playback status on top
+        game.render(tcod);
+        let status = if next_action == replay.actions.len()
+        {
+            "finished".to_string()
+        }
+        else if paused
+        {
+            "paused".to_string()
+        }
+        else
+        {
+            format!("speed {}", speed + 1)
+        };
+
+        tcod.root.set_default_foreground(colors::YELLOW);
+        tcod.root.print_ex(SCREEN_WIDTH - 1, 0, BackgroundFlag::None, TextAlignment::Right, 
+            format!("REPLAY {}/{} ({})", next_action, replay.actions.len(), status));
+        tcod.root.flush();
+    }
+}
@@ -0,0 +1,481 @@
+use crate::game::{ Game, GameRng, PLAYER_ID };
+use crate::fighter::{ NORMAL_SPEED, ACTION_COST };
+use crate::content::Content;
+use crate::paths;
+
+use rand::SeedableRng;
+use serde_json::{ json, Value };
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{ self, File };
+use std::io::{ self, Read, Write };
+use std::path::PathBuf;
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+/// The number of save slots
+pub const SAVE_SLOTS: usize = 5;
+
+/// Where the game was saved before there were save slots
+const LEGACY_SAVE_FILE: &str = "savegame";
+
+/// The version of the save layout written by this version of the game. Any 
+/// change to the saved structures that old saves can't be read into has to 
+/// bump this and add a migration to `MIGRATIONS`.
+pub const SAVE_VERSION: u32 = 1;
+
+/// A function that upgrades a save from one layout to the next
+type Migration = fn(&mut Value) -> Result< (), String >;
+
+/// The migration at index `i` turns a version `i` save into a version `i + 1` save
+const MIGRATIONS: [Migration; SAVE_VERSION as usize] = [
+    migrate_v0_to_v1
+];
+
+/// The reasons a save can fail to load (or be written)
+#[derive(Debug)]
+pub enum SaveError
+{
+    /// There is no saved game
+    Missing,
+
+    /// The save file couldn't be read or written
+    Io(io::Error),
+
+    /// The save file isn't a valid saved game
+    Corrupt(String),
+
+    /// The save was written by a newer version of the game
+    UnsupportedVersion(u32),
+
+    /// The save could be read but the game in it doesn't make sense
+    Invalid(String),
+
+    /// Every save slot is taken, so there's nowhere to put the save
+    NoFreeSlot
+}
+
+impl fmt::Display for SaveError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            SaveError::Missing                  => write!(f, "No saved game to load!"),
+            SaveError::Io(ref e)                => write!(f, "Couldn't access the saved game: {}", e),
+            SaveError::Corrupt(ref e)           => write!(f, "The saved game is corrupt: {}", e),
+            SaveError::UnsupportedVersion(v)    => write!(f, "The saved game is from a newer version of the game (save version {}, this version supports up to {}).", v, SAVE_VERSION),
+            SaveError::Invalid(ref e)           => write!(f, "The saved game is invalid: {}", e),
+            SaveError::NoFreeSlot               => write!(f, "Every save slot is taken.")
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From< io::Error > for SaveError
+{
+    fn from(e: io::Error) -> Self
+    {
+        if e.kind() == io::ErrorKind::NotFound
+        {
+            SaveError::Missing
+        }
+        else
+        {
+            SaveError::Io(e)
+        }
+    }
+}
+
+impl From< serde_json::Error > for SaveError
+{
+    fn from(e: serde_json::Error) -> Self
+    {
+        SaveError::Corrupt(e.to_string())
+    }
+}
+
+/// A summary of a saved game, shown when picking a save slot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveMeta
+{
+    pub character: String,
+    pub level: i32,
+    pub dungeon_level: i32,
+    pub turns: u32,
+
+    /// When the game was saved, in seconds since the unix epoch
+    pub timestamp: u64
+}
+
+impl SaveMeta
+{
+    /// Creates the summary of the given game as of right now
+    fn from_game(game: &Game) -> Self
+    {
+        let player = &game.objects[PLAYER_ID];
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+
+        SaveMeta
+        {
+            character: player.name.clone(),
+            level: player.level,
+            dungeon_level: game.dungeon_level,
+            turns: game.turns,
+            timestamp: timestamp
+        }
+    }
+}
+
+impl fmt::Display for SaveMeta
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}, level {}, depth {}, {} turns ({})", self.character, self.level, self.dungeon_level, self.turns, format_timestamp(self.timestamp))
+    }
+}
+
+/// The envelope every save is written in
+#[derive(Serialize)]
+struct SaveFile< 'a >
+{
+    version: u32,
+    meta: SaveMeta,
+    game: &'a Game
+}
+
+/// Just the summary part of a save, so slots can be listed without loading the games
+#[derive(Deserialize)]
+struct SaveHeader
+{
+    meta: Option< SaveMeta >
+}
+
+/// Returns the directory save slots are kept in
+fn saves_dir() -> PathBuf
+{
+    paths::data_dir().join("saves")
+}
+
+/// Returns the path of the save file for the given slot
+fn slot_path(slot: usize) -> PathBuf
+{
+    saves_dir().join(format!("slot{}.json", slot + 1))
+}
+
+/// Returns what's in each save slot: None for empty slots, and Some(None) for
+/// saves that can't be summarized (e.g. saves from before slots existed)
+pub fn list_slots() -> Vec< Option< Option< SaveMeta > > >
+{
+    (0..SAVE_SLOTS)
+        .map(|slot| {
+            let json_save = fs::read_to_string(slot_path(slot)).ok()?;
+            Some(serde_json::from_str::< SaveHeader >(&json_save).ok().and_then(|h| h.meta))
+        })
+        .collect()
+}
+
+/// Moves a save from before there were save slots into the first free slot,
+/// where it's upgraded like any other old save when it's loaded. Returns the
+/// slot it was moved to, or None if there was nothing to import. The old save
+/// is left alone if every slot is taken, which is a `SaveError::NoFreeSlot`.
+pub fn import_legacy_save() -> Result< Option< usize >, SaveError >
+{
+    let legacy_path = PathBuf::from(LEGACY_SAVE_FILE);
+    if !legacy_path.is_file()
+    {
+        return Ok(None);
+    }
+
+    let slot = match (0..SAVE_SLOTS).find(|&slot| !slot_path(slot).exists())
+    {
+        Some(slot) => slot,
+        None => return Err(SaveError::NoFreeSlot)
+    };
+
+    fs::create_dir_all(saves_dir())?;
+    fs::copy(&legacy_path, slot_path(slot))?;
+    fs::remove_file(&legacy_path)?;
+
+    Ok(Some(slot))
+}
+
+/// Deletes the save in the given slot
+pub fn delete_slot(slot: usize) -> Result< (), SaveError >
+{
+    fs::remove_file(slot_path(slot))?;
+
+    Ok(())
+}
+
+/// Serialize the given game into its save slot using Serde Json. The save is
+/// written to a temporary file first and then renamed over the old save, so a
+/// crash while saving can never leave a half written save behind.
+pub fn save_game(game: &Game) -> Result< (), SaveError >
+{
+    let save_data = serde_json::to_string(&SaveFile { version: SAVE_VERSION, meta: SaveMeta::from_game(game), game: game })?;
+    fs::create_dir_all(saves_dir())?;
+
+    let path = slot_path(game.slot);
+    let tmp_path = path.with_extension("json.tmp");
+    {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(save_data.as_bytes())?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp_path, &path)?;
+
+    Ok(())
+}
+
+/// Deserializes the save in the given slot and loads a game from the data, 
+/// upgrading older save layouts to the current one. The loaded game is restored
+/// with the given content and checked before it's returned.
+pub fn load_game(slot: usize, content: Content) -> Result< Game, SaveError >
+{
+    let mut json_save = String::new();
+    let mut file = File::open(slot_path(slot))?;
+    file.read_to_string(&mut json_save)?;
+
+    let mut game = game_from_json(&json_save, content)?;
+    game.slot = slot;
+
+    Ok(game)
+}
+
+/// Loads a game from the contents of a save file, see `load_game`
+fn game_from_json(json_save: &str, content: Content) -> Result< Game, SaveError >
+{
+    let save: Value = serde_json::from_str(json_save)?;
+
+    // Saves from before versioning are just the bare game
+    let (version, mut game) = match save
+    {
+        Value::Object(mut envelope) if envelope.contains_key("version") && envelope.contains_key("game") =>
+        {
+            let version = envelope["version"].as_u64().ok_or_else(|| SaveError::Corrupt("invalid save version".into()))?;
+            (version as u32, envelope.remove("game").unwrap())
+        }
+
+        game => (0, game)
+    };
+
+    if version > SAVE_VERSION
+    {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+
+    for migration in &MIGRATIONS[version as usize..]
+    {
+        migration(&mut game).map_err(SaveError::Corrupt)?;
+    }
+
+    let mut result = serde_json::from_value::< Game >(game)?;
+    result.restore(content).map_err(SaveError::Invalid)?;
+
+    Ok(result)
+}
+
+/// Version 0 saves are from before seeded games, the object store, per-object
+/// inventories, speed and data-driven items
+fn migrate_v0_to_v1(game: &mut Value) -> Result< (), String >
+{
+    let game = game.as_object_mut().ok_or("the game isn't an object")?;
+
+    // The player's inventory used to live on the game
+    let inventory = game.remove("inventory").unwrap_or_else(|| json!([]));
+    let mut objects = match game.remove("objects")
+    {
+        Some(Value::Array(objects)) if !objects.is_empty() => objects,
+        _ => return Err("the game has no objects".into())
+    };
+    objects[0]["inventory"] = inventory;
+
+    for object in objects.iter_mut()
+    {
+        migrate_object_v0_to_v1(object)?;
+    }
+
+    // The player gets to act first
+    if let Some(fighter) = objects[0].get_mut("fighter").and_then(|f| f.as_object_mut())
+    {
+        fighter.insert("energy".into(), json!(ACTION_COST));
+    }
+
+    // Objects used to be a plain list, the player being the first one
+    let slots: Vec< Value > = objects.into_iter().map(|o| json!({ "generation": 0, "object": o })).collect();
+    game.insert("objects".into(), json!({ "slots": slots, "free": [] }));
+
+    // Old games weren't seeded, so give them a fresh seed. The new seed can't
+    // reproduce the old dungeon, so the game can't be replayed either.
+    let seed: u64 = rand::random();
+    let rng = serde_json::to_value(GameRng::seed_from_u64(seed)).map_err(|e| e.to_string())?;
+    game.insert("seed".into(), json!(seed));
+    game.insert("rng".into(), rng);
+    game.insert("actions".into(), json!([]));
+    game.insert("replayable".into(), json!(false));
+
+    Ok(())
+}
+
+/// Upgrades a single version 0 object and everything in its inventory
+fn migrate_object_v0_to_v1(object: &mut Value) -> Result< (), String >
+{
+    let object = object.as_object_mut().ok_or("an object isn't an object")?;
+    let name = object.get("name").and_then(|n| n.as_str()).unwrap_or("").to_string();
+
+    if let Some(fighter) = object.get_mut("fighter").and_then(|f| f.as_object_mut())
+    {
+        fighter.insert("speed".into(), json!(NORMAL_SPEED));
+        fighter.insert("energy".into(), json!(0));
+    }
+
+    // Items used to be named after what they were rather than what they do
+    let effect = match object.get("item").and_then(|i| i.as_str())
+    {
+        Some("HealthPotion") => Some(json!({ "Heal": { "amount": 5 } })),
+        Some(_) => Some(json!("Equip")),
+        None => None
+    };
+    if let Some(effect) = effect
+    {
+        object.insert("item".into(), effect);
+    }
+
+    // Shields and plate armor used to be put in the wrong slot
+    if let Some(equipment) = object.get_mut("equipment").and_then(|e| e.as_object_mut())
+    {
+        match name.as_str()
+        {
+            "Shield" => { equipment.insert("slot".into(), json!("LeftHand")); },
+            "Plate Armor" => { equipment.insert("slot".into(), json!("Torso")); },
+            _ => {}
+        }
+    }
+
+    let mut inventory = match object.remove("inventory")
+    {
+        Some(Value::Array(inventory)) => inventory,
+        _ => vec![]
+    };
+    for item in inventory.iter_mut()
+    {
+        migrate_object_v0_to_v1(item)?;
+    }
+    object.insert("inventory".into(), Value::Array(inventory));
+
+    Ok(())
+}
+
+/// Formats a unix timestamp as a UTC date and time (YYYY-MM-DD HH:MM)
+fn format_timestamp(timestamp: u64) -> String
+{
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+
+    // Convert days since the epoch into a civil date
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, secs / 3600, (secs % 3600) / 60)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::content::load_content;
+    use crate::item::{ Item, EquipmentSlot };
+
+    /// A version 0 save: a bare game on a small open map, with the player
+    /// first in the list of objects and the player's inventory on the game
+    fn v0_save() -> Value
+    {
+        let floor = json!({ "blocked": false, "blocks_sight": false, "explored": true });
+        let white = json!({ "r": 255, "g": 255, "b": 255 });
+        let fighter = json!({
+            "base_vit": 5, "base_atk": 5, "base_str": 5, "base_def": 5, "base_dex": 5, "base_int": 5, "base_lck": 5,
+            "max_hp": 35, "hp": 20, "xp": 0, "on_death": "PlayerDeath"
+        });
+
+        json!({
+            "map": { "tiles": vec![vec![floor; 5]; 5], "width": 5, "height": 5 },
+            "objects": [
+                {
+                    "pos": [2, 2], "c": "@", "color": white, "name": "Player", "alive": true, "solid": true,
+                    "always_visible": false, "level": 1, "fighter": fighter, "ai": null, "item": null, "equipment": null
+                },
+                {
+                    "pos": [3, 3], "c": "!", "color": white, "name": "Health Potion", "alive": false, "solid": false,
+                    "always_visible": false, "level": 1, "fighter": null, "ai": null, "item": "HealthPotion", "equipment": null
+                }
+            ],
+            "inventory": [
+                {
+                    "pos": [0, 0], "c": "[", "color": white, "name": "Shield", "alive": false, "solid": false,
+                    "always_visible": false, "level": 1, "fighter": null, "ai": null, "item": "Shield",
+                    "equipment": { "slot": "RightHand", "equipped": true, "def_bonus": 1 }
+                }
+            ],
+            "log": [["Welcome!", white]],
+            "dungeon_level": 2
+        })
+    }
+
+    fn content() -> Content
+    {
+        load_content().expect("Failed to load content!")
+    }
+
+    #[test]
+    fn v0_saves_are_migrated()
+    {
+        let game = game_from_json(&v0_save().to_string(), content()).expect("Failed to load the v0 save!");
+
+        let player = &game.objects[PLAYER_ID];
+        assert_eq!(player.pos, (2, 2));
+        assert_eq!(player.fighter.as_ref().map(|f| (f.hp, f.speed, f.energy)), Some((20, NORMAL_SPEED, ACTION_COST)));
+        assert_eq!(player.inventory.len(), 1);
+        assert_eq!(player.inventory[0].item, Some(Item::Equip));
+        assert_eq!(player.inventory[0].equipment.map(|e| e.slot), Some(EquipmentSlot::LeftHand));
+
+        let potion = game.objects.values().find(|o| o.name == "Health Potion").expect("The potion is missing!");
+        assert_eq!(potion.item, Some(Item::Heal { amount: 5 }));
+
+        assert_eq!(game.dungeon_level, 2);
+        assert!(game.actions.is_empty());
+        assert!(!game.replayable);
+    }
+
+    #[test]
+    fn saves_from_newer_versions_are_rejected()
+    {
+        let save = json!({ "version": SAVE_VERSION + 1, "game": v0_save() });
+        match game_from_json(&save.to_string(), content())
+        {
+            Err(SaveError::UnsupportedVersion(version)) => assert_eq!(version, SAVE_VERSION + 1),
+            other => panic!("Expected an unsupported version, got {:?}", other.err())
+        }
+    }
+
+    #[test]
+    fn corrupt_saves_are_rejected()
+    {
+        for json_save in [r#"{ "version": 1, "game": "#, "not json at all", r#"{ "version": "one", "game": {} }"#].iter()
+        {
+            match game_from_json(json_save, content())
+            {
+                Err(SaveError::Corrupt(_)) => {},
+                other => panic!("Expected a corrupt save for {}, got {:?}", json_save, other.err())
+            }
+        }
+    }
+}
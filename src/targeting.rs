@@ -0,0 +1,292 @@
+use crate::{ TCOD, SCREEN_WIDTH };
+use crate::game::{ Game, PLAYER_ID };
+use crate::entity::ObjectId;
+use crate::keymap::Command;
+
+use tcod::colors::{ self, Color };
+use tcod::console::*;
+use tcod::input::{ self, Event, KeyCode };
+
+const LINE_COLOR: Color = colors::LIGHT_YELLOW;
+const BLOCKED_COLOR: Color = colors::LIGHT_RED;
+const AREA_COLOR: Color = colors::DARK_ORANGE;
+
+/// Describes what something (e.g. a scroll) can be aimed at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetSpec
+{
+    /// The furthest the target can be from the player, None for anywhere in view
+    pub range: Option< i32 >,
+
+    /// The radius of the area around the target that's affected, 0 for just the target
+    pub radius: i32,
+
+    /// Whether the target has to be a living monster rather than any tile
+    pub monster_only: bool
+}
+
+impl TargetSpec
+{
+    /// Returns true if the given position can be targeted by the player: it has
+    /// to be in view, in range, in the line of fire and, if required, have a
+    /// monster on it
+    pub fn is_valid(&self, game: &Game, pos: (i32, i32)) -> bool
+    {
+        let player_pos = game.objects[PLAYER_ID].pos;
+        game.map.in_bounds(pos) &&
+            game.map.is_in_fov(pos) &&
+            self.range.map_or(true, |r| distance(player_pos, pos) <= r as f32) &&
+            line_of_fire(game, player_pos, pos).last() == Some(&pos) &&
+            (!self.monster_only || monster_at(game, pos).is_some())
+    }
+}
+
+/// Returns the tiles on the straight line between two positions, excluding the
+/// start. The line stops early at the first wall or solid object in the way,
+/// which is where anything fired along it would hit.
+pub fn line_of_fire(game: &Game, from: (i32, i32), to: (i32, i32)) -> Vec< (i32, i32) >
+{
+    let mut line = vec![];
+    for pos in bresenham(from, to).into_iter().skip(1)
+    {
+        line.push(pos);
+        if !game.map.in_bounds(pos) || game.map.is_blocked(pos.0, pos.1, &game.objects)
+        {
+            break;
+        }
+    }
+
+    line
+}
+
+/// Returns true if no wall blocks the view along the straight line between two
+/// positions. Unlike the player's field of view this works from anywhere, so
+/// it's what monsters see with.
+pub fn line_of_sight(game: &Game, from: (i32, i32), to: (i32, i32)) -> bool
+{
+    bresenham(from, to)
+        .into_iter()
+        .filter(|&pos| pos != from && pos != to)
+        .all(|(x, y)| game.map.in_bounds((x, y)) && !game.map.tiles[x as usize][y as usize].blocks_sight)
+}
+
+/// Returns every tile within the given radius of a position that the position
+/// has a line of sight to, whether or not the player can see it
+pub fn area(game: &Game, center: (i32, i32), radius: i32) -> Vec< (i32, i32) >
+{
+    let mut tiles = vec![];
+    for x in (center.0 - radius)..=(center.0 + radius)
+    {
+        for y in (center.1 - radius)..=(center.1 + radius)
+        {
+            if game.map.in_bounds((x, y)) && distance(center, (x, y)) <= radius as f32 && line_of_sight(game, center, (x, y))
+            {
+                tiles.push((x, y));
+            }
+        }
+    }
+
+    tiles
+}
+
+/// Returns the living monster at the given position, if there is one
+pub fn monster_at(game: &Game, pos: (i32, i32)) -> Option< ObjectId >
+{
+    game.objects
+        .iter()
+        .find(|(id, o)| *id != PLAYER_ID && o.pos == pos && o.alive && o.fighter.is_some())
+        .map(|(id, _)| id)
+}
+
+/// Returns the closest living monster in view that can be targeted with the given spec
+pub fn nearest_monster(game: &Game, spec: &TargetSpec) -> Option< ObjectId >
+{
+    let player_pos = game.objects[PLAYER_ID].pos;
+    targetable_monsters(game, spec)
+        .into_iter()
+        .min_by(|a, b| {
+            let (a, b) = (distance(player_pos, game.objects[*a].pos), distance(player_pos, game.objects[*b].pos));
+            a.partial_cmp(&b).unwrap()
+        })
+}
+
+/// Returns every living monster that can be targeted with the given spec
+fn targetable_monsters(game: &Game, spec: &TargetSpec) -> Vec< ObjectId >
+{
+    game.objects
+        .iter()
+        .filter(|(id, o)| *id != PLAYER_ID && o.alive && o.fighter.is_some() && spec.is_valid(game, o.pos))
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// Lets the player pick a target on the map with the mouse or the movement
+/// keys, previewing the line of fire and the affected area. The cursor starts
+/// on the nearest monster in range. Tab cycles through the monsters in range,
+/// a click, enter or the interact key confirms and escape or a right click
+/// cancels. Returns None if targeting was cancelled.
+pub fn select_target(game: &mut Game, spec: &TargetSpec, tcod: &mut TCOD) -> Option< (i32, i32) >
+{
+    let player_pos = game.objects[PLAYER_ID].pos;
+    let mut cursor = nearest_monster(game, spec).map_or(player_pos, |id| game.objects[id].pos);
+    let mut cycle = 0;
+
+    while !tcod.root.window_closed()
+    {
+        game.render(tcod);
+        render_preview(game, spec, cursor, tcod);
+        tcod.root.flush();
+
+        let mut confirmed = false;
+        match input::check_for_event(input::MOUSE | input::KEY_PRESS)
+        {
+            Some((_, Event::Mouse(m))) =>
+            {
+                tcod.mouse = m;
+                let pos = (m.cx as i32, m.cy as i32);
+                if game.map.in_bounds(pos)
+                {
+                    cursor = pos;
+                }
+
+                if m.rbutton_pressed
+                {
+                    return None;
+                }
+                confirmed = m.lbutton_pressed;
+            },
+
+            Some((_, Event::Key(key))) =>
+            {
+                match (key.code, tcod.keymap.command(key))
+                {
+                    (KeyCode::Enter, _) | (_, Some(Command::Interact)) => confirmed = true,
+                    (_, Some(Command::Exit)) => return None,
+                    (KeyCode::Tab, _) =>
+                    {
+                        let monsters = targetable_monsters(game, spec);
+                        if !monsters.is_empty()
+                        {
+                            cycle = (cycle + 1) % monsters.len();
+                            cursor = game.objects[monsters[cycle]].pos;
+                        }
+                    },
+                    (_, Some(command)) =>
+                    {
+                        if let Some((dx, dy)) = command.direction()
+                        {
+                            if game.map.in_bounds((cursor.0 + dx, cursor.1 + dy))
+                            {
+                                cursor = (cursor.0 + dx, cursor.1 + dy);
+                            }
+                        }
+                    },
+                    _ => {}
+                }
+            },
+
+            _ => {}
+        }
+
+        if confirmed && spec.is_valid(game, cursor)
+        {
+            return Some(cursor);
+        }
+    }
+
+    None
+}
+
+/// Highlights the line of fire to the cursor and the area around it that
+/// would be affected
+fn render_preview(game: &Game, spec: &TargetSpec, cursor: (i32, i32), tcod: &mut TCOD)
+{
+    let player_pos = game.objects[PLAYER_ID].pos;
+    let valid = spec.is_valid(game, cursor);
+
+    if spec.radius > 0 && valid
+    {
+        // Only show the part of the blast over tiles the player knows about
+        for (x, y) in area(game, cursor, spec.radius).into_iter().filter(|&pos| game.map.is_explored(pos))
+        {
+            tcod.root.set_char_background(x, y, AREA_COLOR, BackgroundFlag::Set);
+        }
+    }
+
+    for (x, y) in line_of_fire(game, player_pos, cursor)
+    {
+        tcod.root.set_char_background(x, y, LINE_COLOR, BackgroundFlag::Set);
+    }
+
+    let cursor_color = if valid { LINE_COLOR } else { BLOCKED_COLOR };
+    tcod.root.set_char_background(cursor.0, cursor.1, cursor_color, BackgroundFlag::Set);
+
+    // Show whichever keys the player has bound to confirming and cancelling
+    let mut confirm_keys = vec!["Enter", "click"];
+    confirm_keys.extend(tcod.keymap.keys(Command::Interact));
+    let mut cancel_keys = tcod.keymap.keys(Command::Exit);
+    cancel_keys.push("right click");
+    let help = format!("{}: choose target  Tab: next monster  {}: cancel", confirm_keys.join("/"), cancel_keys.join("/"));
+
+    tcod.root.set_default_foreground(colors::WHITE);
+    tcod.root.print_ex(SCREEN_WIDTH / 2, 0, BackgroundFlag::None, TextAlignment::Center, help);
+}
+
+/// Returns the straight-line distance between two positions
+fn distance(a: (i32, i32), b: (i32, i32)) -> f32
+{
+    (((a.0 - b.0).pow(2) + (a.1 - b.1).pow(2)) as f32).sqrt()
+}
+
+/// Returns every tile on the line between two positions, including both ends
+fn bresenham(from: (i32, i32), to: (i32, i32)) -> Vec< (i32, i32) >
+{
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let mut error = dx + dy;
+    let mut pos = from;
+    let mut line = vec![pos];
+
+    while pos != to
+    {
+        let e2 = 2 * error;
+        if e2 >= dy
+        {
+            error += dy;
+            pos.0 += sx;
+        }
+        if e2 <= dx
+        {
+            error += dx;
+            pos.1 += sy;
+        }
+        line.push(pos);
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::content::load_content;
+    use crate::map::Map;
+
+    #[test]
+    fn areas_are_blocked_by_walls_rather_than_the_players_view()
+    {
+        // None of the fresh open map is in the player's FOV
+        let mut game = Game::new(42, load_content().expect("Failed to load content!"));
+        game.map = Map::new();
+        game.map.tiles[12][10].blocked = true;
+        game.map.tiles[12][10].blocks_sight = true;
+
+        let tiles = area(&game, (10, 10), 3);
+        assert!(tiles.contains(&(10, 10)));
+        assert!(tiles.contains(&(7, 10)));
+        assert!(tiles.contains(&(12, 10)));
+        assert!(!tiles.contains(&(13, 10)));
+        assert!(!tiles.contains(&(10, 14)));
+    }
+}